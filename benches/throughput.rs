@@ -0,0 +1,97 @@
+//! Encode and decode throughput on a fixed corpus and on synthetic skewed
+//! and uniform inputs. Run with `cargo bench`; pass a substring to only run
+//! the matching cases, e.g. `cargo bench -- skewed`.
+//!
+//! This is a plain `harness = false` binary timed with `Instant`, so it
+//! needs nothing beyond the standard library.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use huffman_challenge::counter::count_chars;
+use huffman_challenge::HuffmanCode;
+
+/// Each case runs for at least this long so short inputs still give
+/// stable numbers.
+const MIN_RUN_TIME: Duration = Duration::from_millis(500);
+const INPUT_BYTES: usize = 1024 * 1024;
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+    let corpus = include_str!("../tests/fixtures/sample.txt");
+    let inputs = [
+        ("corpus", repeat_to_len(corpus, INPUT_BYTES)),
+        ("skewed", skewed_text(INPUT_BYTES)),
+        ("uniform", uniform_text(INPUT_BYTES)),
+    ];
+
+    for (name, text) in &inputs {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+        let code = HuffmanCode::from_frequencies(&count_chars(text)).unwrap();
+        let bits = code.encode(text).unwrap();
+
+        let encode = measure(|| code.encode(black_box(text)).unwrap());
+        let decode = measure(|| code.decode(black_box(&bits)).unwrap());
+        println!(
+            "{:<8} encode {:>8.1} MB/s   decode {:>8.1} MB/s   ({:.2} bits/char)",
+            name,
+            throughput(text.len(), encode),
+            throughput(text.len(), decode),
+            bits.len() as f64 / text.chars().count() as f64
+        );
+    }
+}
+
+/// Average time of one call to `f`, repeated for at least `MIN_RUN_TIME`.
+fn measure<T>(mut f: impl FnMut() -> T) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < MIN_RUN_TIME {
+        black_box(f());
+        iterations += 1;
+    }
+    start.elapsed() / iterations
+}
+
+fn throughput(bytes: usize, per_iteration: Duration) -> f64 {
+    bytes as f64 / per_iteration.as_secs_f64() / 1_000_000.0
+}
+
+fn repeat_to_len(text: &str, len: usize) -> String {
+    text.repeat(len.div_ceil(text.len()))
+}
+
+/// Geometric-like distribution: 'a' is about half of the text, 'b' a
+/// quarter and so on, which gives very short codes for the common chars.
+fn skewed_text(len: usize) -> String {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..len)
+        .map(|_| {
+            let r = xorshift(&mut state);
+            (b'a' + (r.trailing_ones() as u8).min(25)) as char
+        })
+        .collect()
+}
+
+/// Printable ASCII with every char equally likely, close to the worst case
+/// for a static code.
+fn uniform_text(len: usize) -> String {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    (0..len)
+        .map(|_| (b' ' + (xorshift(&mut state) % 95) as u8) as char)
+        .collect()
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
@@ -0,0 +1,269 @@
+use std::io::{self, Read, Write};
+
+use crate::bits::{BitReader, BitWriter};
+
+const SYMBOL_COUNT: usize = 256;
+/// A full tree has one leaf per byte value plus the NYT leaf.
+const MAX_NODES: usize = 2 * (SYMBOL_COUNT + 1) - 1;
+const ROOT: usize = MAX_NODES - 1;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AdaptiveNode {
+    weight: u64,
+    parent: Option<usize>,
+    children: Option<(usize, usize)>,
+    symbol: Option<u8>,
+}
+
+/// Adaptive Huffman coder over bytes using the FGK algorithm.
+///
+/// Encoder and decoder start from the same empty model and update it after
+/// every symbol, so no table needs to be transmitted and the input is read
+/// in a single pass. Bytes not seen yet are sent as the code of the NYT
+/// ("not yet transmitted") leaf, a `0` flag bit and the 8 raw bits. The end
+/// of the stream is the NYT code followed by a `1` flag bit.
+///
+/// Nodes are stored by their FGK number: the root has the highest index and
+/// weights never decrease with the index (the sibling property).
+#[derive(Debug)]
+pub struct AdaptiveHuffman {
+    nodes: Vec<AdaptiveNode>,
+    leaves: [Option<usize>; SYMBOL_COUNT],
+    nyt: usize,
+}
+
+impl AdaptiveHuffman {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![AdaptiveNode::default(); MAX_NODES],
+            leaves: [None; SYMBOL_COUNT],
+            nyt: ROOT,
+        }
+    }
+
+    /// Encodes all of `input` to `output` in one pass and returns the number
+    /// of bits written, including the end marker but not the final padding.
+    pub fn encode<R: Read, W: Write>(input: R, output: W) -> io::Result<u64> {
+        let mut model = Self::new();
+        let mut writer = BitWriter::new(output);
+        let mut path = Vec::new();
+
+        for byte in io::BufReader::new(input).bytes() {
+            let byte = byte?;
+            match model.leaves[byte as usize] {
+                Some(leaf) => {
+                    model.path_to(leaf, &mut path);
+                    writer.write_bits(&path)?;
+                }
+                None => {
+                    model.path_to(model.nyt, &mut path);
+                    writer.write_bits(&path)?;
+                    writer.write_bit(false)?;
+                    for shift in (0..8).rev() {
+                        writer.write_bit((byte >> shift) & 1 == 1)?;
+                    }
+                }
+            }
+            model.update(byte);
+        }
+
+        model.path_to(model.nyt, &mut path);
+        writer.write_bits(&path)?;
+        writer.write_bit(true)?;
+        writer.flush()?;
+        Ok(writer.bits_written())
+    }
+
+    /// Decodes a stream written by `encode`, mirroring the encoder's model
+    /// updates. Bits after the end marker are ignored.
+    pub fn decode<R: Read, W: Write>(input: R, mut output: W) -> io::Result<()> {
+        let mut model = Self::new();
+        let mut reader = BitReader::new(input);
+        let mut decoded = Vec::new();
+        let mut read_bit = || {
+            reader.read_bit()?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "adaptive stream ends before its end marker",
+                )
+            })
+        };
+
+        loop {
+            let mut node = ROOT;
+            while let Some((left, right)) = model.nodes[node].children {
+                node = if read_bit()? { right } else { left };
+            }
+
+            let byte = if node == model.nyt {
+                if read_bit()? {
+                    break;
+                }
+                let mut byte = 0u8;
+                for _ in 0..8 {
+                    byte = (byte << 1) | read_bit()? as u8;
+                }
+                byte
+            } else {
+                model.nodes[node].symbol.unwrap()
+            };
+
+            decoded.push(byte);
+            if decoded.len() >= 8 * 1024 {
+                output.write_all(&decoded)?;
+                decoded.clear();
+            }
+            model.update(byte);
+        }
+        output.write_all(&decoded)?;
+        output.flush()
+    }
+
+    /// Writes the code of `node` (root to node, left = 0) into `path`.
+    fn path_to(&self, mut node: usize, path: &mut Vec<bool>) {
+        path.clear();
+        while let Some(parent) = self.nodes[node].parent {
+            let (_, right) = self.nodes[parent].children.unwrap();
+            path.push(node == right);
+            node = parent;
+        }
+        path.reverse();
+    }
+
+    fn update(&mut self, byte: u8) {
+        let mut node = match self.leaves[byte as usize] {
+            Some(leaf) => leaf,
+            None => self.spawn_leaf(byte),
+        };
+
+        loop {
+            let leader = self.block_leader(node);
+            if leader != node && Some(leader) != self.nodes[node].parent {
+                self.swap(node, leader);
+                node = leader;
+            }
+            self.nodes[node].weight += 1;
+            match self.nodes[node].parent {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+    }
+
+    /// Splits the NYT leaf into a new NYT leaf and a zero-weight leaf for
+    /// `byte`, returning the new leaf.
+    fn spawn_leaf(&mut self, byte: u8) -> usize {
+        let parent = self.nyt;
+        let leaf = parent - 1;
+        let nyt = parent - 2;
+
+        self.nodes[leaf] = AdaptiveNode {
+            weight: 0,
+            parent: Some(parent),
+            children: None,
+            symbol: Some(byte),
+        };
+        self.nodes[nyt] = AdaptiveNode {
+            weight: 0,
+            parent: Some(parent),
+            children: None,
+            symbol: None,
+        };
+        self.nodes[parent].children = Some((nyt, leaf));
+        self.leaves[byte as usize] = Some(leaf);
+        self.nyt = nyt;
+        leaf
+    }
+
+    /// Highest-numbered node with the same weight as `node`.
+    fn block_leader(&self, node: usize) -> usize {
+        let weight = self.nodes[node].weight;
+        let mut leader = node;
+        while leader < ROOT && self.nodes[leader + 1].weight == weight {
+            leader += 1;
+        }
+        leader
+    }
+
+    /// Exchanges the subtrees at positions `a` and `b`. Each position keeps
+    /// its parent, so the subtrees trade places in the tree.
+    fn swap(&mut self, a: usize, b: usize) {
+        let parent_a = self.nodes[a].parent;
+        let parent_b = self.nodes[b].parent;
+        self.nodes.swap(a, b);
+        self.nodes[a].parent = parent_a;
+        self.nodes[b].parent = parent_b;
+
+        for position in [a, b] {
+            let node = self.nodes[position];
+            if let Some((left, right)) = node.children {
+                self.nodes[left].parent = Some(position);
+                self.nodes[right].parent = Some(position);
+            }
+            if let Some(symbol) = node.symbol {
+                self.leaves[symbol as usize] = Some(position);
+            }
+        }
+    }
+}
+
+impl Default for AdaptiveHuffman {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        AdaptiveHuffman::encode(input, &mut encoded).unwrap();
+        let mut decoded = Vec::new();
+        AdaptiveHuffman::decode(encoded.as_slice(), &mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_round_trip_text() {
+        let input = "adaptive huffman needs no header, it learns as it goes\n".repeat(50);
+        assert_eq!(round_trip(input.as_bytes()), input.as_bytes());
+    }
+
+    #[test]
+    fn test_round_trip_all_byte_values() {
+        let input: Vec<u8> = (0..=255u8)
+            .chain((0..=255u8).rev())
+            .chain([0, 0, 255])
+            .collect();
+        assert_eq!(round_trip(&input), input);
+    }
+
+    #[test]
+    fn test_round_trip_empty_and_single_byte() {
+        assert_eq!(round_trip(b""), b"");
+        assert_eq!(round_trip(b"x"), b"x");
+        assert_eq!(round_trip(b"xxxxxxxx"), b"xxxxxxxx");
+    }
+
+    #[test]
+    fn test_skewed_input_compresses() {
+        let input: Vec<u8> = (0..10_000)
+            .map(|i| if i % 10 == 0 { b'b' } else { b'a' })
+            .collect();
+        let mut encoded = Vec::new();
+        let bit_len = AdaptiveHuffman::encode(input.as_slice(), &mut encoded).unwrap();
+        assert_eq!(encoded.len() as u64, bit_len.div_ceil(8));
+        assert!(encoded.len() < input.len() / 4);
+    }
+
+    #[test]
+    fn test_decode_truncated_stream() {
+        let mut encoded = Vec::new();
+        AdaptiveHuffman::encode(&b"truncate me"[..], &mut encoded).unwrap();
+        encoded.truncate(encoded.len() / 2);
+        let err = AdaptiveHuffman::decode(encoded.as_slice(), Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,193 @@
+//! Several files bundled into one archive, each compressed on its own.
+//!
+//! Every entry holds a complete byte container, with its own frequency
+//! table, so files with different contents each get a code that suits
+//! them and can be decoded independently.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::huffman_encoder::{
+    compress_symbols, read_container, ByteReader, DecodeError, EncodeOptions, FormatError,
+};
+
+const ARCHIVE_MAGIC: &[u8; 4] = b"HUFA";
+const ARCHIVE_VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The archive or one of its containers is malformed.
+    Format(FormatError),
+    /// A container's payload does not decode or fails its checksum.
+    Decode(DecodeError),
+    /// An entry name is empty, not a plain file name, or not UTF-8.
+    InvalidName(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Format(e) => write!(f, "invalid archive: {}", e),
+            ArchiveError::Decode(e) => write!(f, "corrupt archive entry: {}", e),
+            ArchiveError::InvalidName(name) => {
+                write!(f, "{:?} is not a valid archive entry name", name)
+            }
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+impl From<FormatError> for ArchiveError {
+    fn from(e: FormatError) -> Self {
+        ArchiveError::Format(e)
+    }
+}
+
+impl From<DecodeError> for ArchiveError {
+    fn from(e: DecodeError) -> Self {
+        ArchiveError::Decode(e)
+    }
+}
+
+/// A file read back from an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Bundles `(name, data)` pairs into an archive. Names must be plain file
+/// names, without directories, so that extracting can never write outside
+/// the target directory.
+///
+/// Layout, all integers big-endian:
+///
+/// ```text
+/// magic        4 bytes   b"HUFA"
+/// version      1 byte    ARCHIVE_VERSION
+/// entry count  4 bytes
+/// entries      per file: name length (4 bytes), UTF-8 name,
+///              container length (8 bytes), byte container
+/// ```
+pub fn write_archive<'a, I>(files: I) -> Result<Vec<u8>, ArchiveError>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    let mut entries = Vec::new();
+    for (name, data) in files {
+        check_name(name)?;
+        entries.push((name, compress_symbols(data, &EncodeOptions::default())));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(ARCHIVE_MAGIC);
+    out.push(ARCHIVE_VERSION);
+    out.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (name, container) in entries {
+        out.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(container.len() as u64).to_be_bytes());
+        out.extend_from_slice(&container);
+    }
+    Ok(out)
+}
+
+/// Reads and decodes every entry of an archive written by `write_archive`,
+/// verifying each entry's checksum.
+pub fn read_archive(data: &[u8]) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+    let mut reader = ByteReader::new(data);
+    if reader.take(ARCHIVE_MAGIC.len())? != ARCHIVE_MAGIC {
+        return Err(FormatError::BadMagic.into());
+    }
+    let version = reader.read_u8()?;
+    if version != ARCHIVE_VERSION {
+        return Err(FormatError::UnsupportedVersion(version).into());
+    }
+
+    let entry_count = reader.read_u32()?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        let name_len = reader.read_u32()? as usize;
+        let name_bytes = reader.take(name_len)?;
+        let name = String::from_utf8(name_bytes.to_vec())
+            .map_err(|_| ArchiveError::InvalidName(String::from_utf8_lossy(name_bytes).into()))?;
+        check_name(&name)?;
+
+        let container_len =
+            usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?;
+        let container = read_container::<u8>(reader.take(container_len)?)?;
+        entries.push(ArchiveEntry {
+            name,
+            data: container.decode_symbols()?,
+        });
+    }
+    Ok(entries)
+}
+
+fn check_name(name: &str) -> Result<(), ArchiveError> {
+    let plain =
+        !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0']);
+    if !plain {
+        return Err(ArchiveError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let text = "the first file, with some text in it\n".repeat(20);
+        let binary: Vec<u8> = (0..=255).collect();
+        let files: [(&str, &[u8]); 3] = [
+            ("notes.txt", text.as_bytes()),
+            ("bytes.bin", &binary),
+            ("empty", b""),
+        ];
+
+        let archive = write_archive(files).unwrap();
+        assert_eq!(&archive[..4], ARCHIVE_MAGIC);
+        let entries = read_archive(&archive).unwrap();
+        assert_eq!(entries.len(), 3);
+        for (entry, (name, data)) in entries.iter().zip(files) {
+            assert_eq!(entry.name, name);
+            assert_eq!(entry.data, data);
+        }
+    }
+
+    #[test]
+    fn test_archive_rejects_path_names() {
+        for name in ["", ".", "..", "dir/file", "..\\up", "nul\0"] {
+            assert_eq!(
+                write_archive([(name, &b"data"[..])]).unwrap_err(),
+                ArchiveError::InvalidName(name.to_string())
+            );
+        }
+
+        let mut archive = write_archive([("abc", &b"data"[..])]).unwrap();
+        // Patch the stored name to "a/c" after the fact.
+        archive[14] = b'/';
+        assert_eq!(
+            read_archive(&archive).unwrap_err(),
+            ArchiveError::InvalidName("a/c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_archive_rejects_truncated_and_corrupt_input() {
+        let archive = write_archive([("a.txt", &b"aaaabbbcc"[..])]).unwrap();
+        for len in 0..archive.len() {
+            assert!(read_archive(&archive[..len]).is_err(), "length {}", len);
+        }
+
+        let mut corrupt = archive.clone();
+        corrupt[0] = b'X';
+        assert_eq!(
+            read_archive(&corrupt).unwrap_err(),
+            ArchiveError::Format(FormatError::BadMagic)
+        );
+    }
+}
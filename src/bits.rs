@@ -0,0 +1,434 @@
+use std::io::{self, Read, Write};
+
+const WRITE_BUFFER_SIZE: usize = 8 * 1024;
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Order in which bits fill each byte. Most formats, and the containers
+/// written here by default, fill bytes from the most significant bit;
+/// DEFLATE and some others start from the least significant one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst = 0,
+    LsbFirst = 1,
+}
+
+impl BitOrder {
+    /// Mask selecting the bit at `index` (0 to 7) of a byte, counted in
+    /// this order.
+    fn mask(self, index: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => 0x80 >> index,
+            BitOrder::LsbFirst => 1 << index,
+        }
+    }
+}
+
+/// Packs `bits` into bytes, most significant bit first. The final byte is
+/// padded with zeros, so the bit length has to be stored separately to undo
+/// the packing with `unpack_bits`.
+pub fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    pack_bits_with_order(bits, BitOrder::MsbFirst)
+}
+
+/// Packs `bits` like `pack_bits`, filling each byte in the given order.
+pub fn pack_bits_with_order(bits: &[bool], order: BitOrder) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, &bit) in bits.iter().enumerate() {
+        if bit {
+            bytes[i / 8] |= order.mask((i % 8) as u8);
+        }
+    }
+    bytes
+}
+
+/// Unpacks the first `bit_len` bits of `bytes`, most significant bit first,
+/// dropping the padding added by `pack_bits`.
+pub fn unpack_bits(bytes: &[u8], bit_len: usize) -> Vec<bool> {
+    unpack_bits_with_order(bytes, bit_len, BitOrder::MsbFirst)
+}
+
+/// Unpacks bits written by `pack_bits_with_order` with the same order.
+pub fn unpack_bits_with_order(bytes: &[u8], bit_len: usize, order: BitOrder) -> Vec<bool> {
+    assert!(
+        bit_len <= bytes.len() * 8,
+        "bit length {} exceeds the {} available bits",
+        bit_len,
+        bytes.len() * 8
+    );
+    (0..bit_len)
+        .map(|i| bytes[i / 8] & order.mask((i % 8) as u8) != 0)
+        .collect()
+}
+
+/// Writes individual bits to an underlying writer, most significant bit
+/// first unless `with_bit_order` says otherwise. Completed bytes are
+/// buffered and handed to the writer in batches; `flush` zero-pads the last
+/// partial byte and writes everything out.
+pub struct BitWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    capacity: usize,
+    current_byte: u8,
+    pending_bits: u8,
+    bits_written: u64,
+    order: BitOrder,
+}
+
+impl<W: Write> BitWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(inner, WRITE_BUFFER_SIZE)
+    }
+
+    /// Creates a writer that hands completed bytes to `inner` once
+    /// `capacity` of them are buffered.
+    pub fn with_capacity(inner: W, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            inner,
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            current_byte: 0,
+            pending_bits: 0,
+            bits_written: 0,
+            order: BitOrder::MsbFirst,
+        }
+    }
+
+    /// Makes the writer fill bytes in `order`. Set it before writing: bits
+    /// already in the current byte are not rearranged.
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        if bit {
+            self.current_byte |= self.order.mask(self.pending_bits);
+        }
+        self.pending_bits += 1;
+        self.bits_written += 1;
+        if self.pending_bits == 8 {
+            self.complete_byte()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the low `len` bits of `bits`, most significant first. Whole
+    /// runs of bits are ORed into the current byte at once, which is faster
+    /// than `write_bits` for codes kept as integers.
+    pub fn write_packed(&mut self, bits: u64, len: u8) -> io::Result<()> {
+        assert!(len <= 64, "cannot write {} bits from a u64", len);
+        if self.order == BitOrder::LsbFirst {
+            // Runs would land in the byte reversed; go bit by bit instead.
+            for i in (0..len).rev() {
+                self.write_bit((bits >> i) & 1 == 1)?;
+            }
+            return Ok(());
+        }
+        let mut remaining = len;
+        while remaining > 0 {
+            let free = 8 - self.pending_bits;
+            let take = free.min(remaining);
+            let run = (bits >> (remaining - take)) as u8 & (0xFF >> (8 - take));
+            self.current_byte |= run << (free - take);
+            self.pending_bits += take;
+            self.bits_written += take as u64;
+            remaining -= take;
+            if self.pending_bits == 8 {
+                self.complete_byte()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn complete_byte(&mut self) -> io::Result<()> {
+        self.buffer.push(self.current_byte);
+        self.current_byte = 0;
+        self.pending_bits = 0;
+        if self.buffer.len() >= self.capacity {
+            self.inner.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    pub fn write_bits(&mut self, bits: &[bool]) -> io::Result<()> {
+        for &bit in bits {
+            self.write_bit(bit)?;
+        }
+        Ok(())
+    }
+
+    /// Pads the current byte with zeros, if it is partially filled, and
+    /// writes all buffered bytes. Bits written afterwards start a new byte.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending_bits > 0 {
+            self.buffer.push(self.current_byte);
+            self.current_byte = 0;
+            self.pending_bits = 0;
+        }
+        self.inner.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.inner.flush()
+    }
+
+    /// Number of bits passed to `write_bit`, not counting flush padding.
+    pub fn bits_written(&self) -> u64 {
+        self.bits_written
+    }
+
+    /// Returns the underlying writer. Call `flush` first, buffered bits are
+    /// discarded otherwise.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads individual bits from an underlying reader, most significant bit
+/// first unless `with_bit_order` says otherwise. Input is pulled in chunks
+/// but consumed one byte at a time.
+pub struct BitReader<R: Read> {
+    inner: R,
+    buffer: Vec<u8>,
+    buffer_pos: usize,
+    current_byte: u8,
+    remaining_bits: u8,
+    order: BitOrder,
+}
+
+impl<R: Read> BitReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: Vec::with_capacity(READ_BUFFER_SIZE),
+            buffer_pos: 0,
+            current_byte: 0,
+            remaining_bits: 0,
+            order: BitOrder::MsbFirst,
+        }
+    }
+
+    /// Makes the reader take bits from each byte in `order`, which must
+    /// match the order the data was written in.
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Returns the next bit, or `None` once the underlying reader is
+    /// exhausted. Padding bits of the last byte are returned like any other.
+    pub fn read_bit(&mut self) -> io::Result<Option<bool>> {
+        if self.remaining_bits == 0 {
+            match self.next_byte()? {
+                Some(byte) => {
+                    self.current_byte = byte;
+                    self.remaining_bits = 8;
+                }
+                None => return Ok(None),
+            }
+        }
+        let index = 8 - self.remaining_bits;
+        self.remaining_bits -= 1;
+        Ok(Some(self.current_byte & self.order.mask(index) != 0))
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.buffer_pos == self.buffer.len() {
+            self.buffer.resize(READ_BUFFER_SIZE, 0);
+            let n = loop {
+                match self.inner.read(&mut self.buffer) {
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        self.buffer.clear();
+                        self.buffer_pos = 0;
+                        return Err(e);
+                    }
+                }
+            };
+            self.buffer.truncate(n);
+            self.buffer_pos = 0;
+            if n == 0 {
+                return Ok(None);
+            }
+        }
+        let byte = self.buffer[self.buffer_pos];
+        self.buffer_pos += 1;
+        Ok(Some(byte))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits_from_str(s: &str) -> Vec<bool> {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn test_pack_exact_byte() {
+        let bits = bits_from_str("10110001");
+        assert_eq!(pack_bits(&bits), vec![0b1011_0001]);
+        assert_eq!(unpack_bits(&[0b1011_0001], 8), bits);
+    }
+
+    #[test]
+    fn test_pack_multiple_bytes() {
+        let bits = bits_from_str("1111000000001111");
+        assert_eq!(pack_bits(&bits), vec![0xF0, 0x0F]);
+        assert_eq!(unpack_bits(&[0xF0, 0x0F], 16), bits);
+    }
+
+    #[test]
+    fn test_pack_five_bits() {
+        let bits = bits_from_str("10101");
+        let packed = pack_bits(&bits);
+        assert_eq!(packed, vec![0b1010_1000]);
+        assert_eq!(unpack_bits(&packed, 5), bits);
+    }
+
+    #[test]
+    fn test_pack_thirteen_bits() {
+        let bits = bits_from_str("1100110011111");
+        let packed = pack_bits(&bits);
+        assert_eq!(packed, vec![0b1100_1100, 0b1111_1000]);
+        assert_eq!(unpack_bits(&packed, 13), bits);
+    }
+
+    #[test]
+    fn test_pack_empty() {
+        assert!(pack_bits(&[]).is_empty());
+        assert!(unpack_bits(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_bit_writer_partial_byte() {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(&bits_from_str("101")).unwrap();
+        assert_eq!(writer.bits_written(), 3);
+        writer.flush().unwrap();
+        assert_eq!(writer.into_inner(), vec![0b1010_0000]);
+    }
+
+    #[test]
+    fn test_bit_writer_matches_pack_bits() {
+        let bits = bits_from_str("1100110011111");
+        let mut writer = BitWriter::new(Vec::new());
+        for &bit in &bits {
+            writer.write_bit(bit).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(writer.bits_written(), 13);
+        assert_eq!(writer.into_inner(), pack_bits(&bits));
+    }
+
+    #[test]
+    fn test_bit_writer_multiple_flushes() {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(&bits_from_str("11")).unwrap();
+        writer.flush().unwrap();
+        writer.write_bits(&bits_from_str("000000001")).unwrap();
+        writer.flush().unwrap();
+        // Flushing with nothing pending must not emit an extra byte.
+        writer.flush().unwrap();
+        assert_eq!(writer.bits_written(), 11);
+        assert_eq!(writer.into_inner(), vec![0b1100_0000, 0x00, 0b1000_0000]);
+    }
+
+    #[test]
+    fn test_bit_writer_large_output() {
+        let bits: Vec<bool> = (0..200_000).map(|i| i % 3 == 0).collect();
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(&bits).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.into_inner(), pack_bits(&bits));
+    }
+
+    #[test]
+    fn test_write_packed_matches_write_bits() {
+        let codes: [(u64, u8); 6] = [
+            (0b1, 1),
+            (0b01101, 5),
+            (0, 3),
+            (u64::MAX, 64),
+            (0b1_0110_0111, 9),
+            (1 << 40 | 0b11, 41),
+        ];
+        let mut packed = BitWriter::new(Vec::new());
+        let mut expected = Vec::new();
+        for (bits, len) in codes {
+            packed.write_packed(bits, len).unwrap();
+            expected.extend((0..len).rev().map(|i| (bits >> i) & 1 == 1));
+        }
+        packed.write_packed(0xFF, 0).unwrap();
+        packed.flush().unwrap();
+        assert_eq!(packed.bits_written(), expected.len() as u64);
+        assert_eq!(packed.into_inner(), pack_bits(&expected));
+    }
+
+    #[test]
+    fn test_lsb_first_order() {
+        let bits = bits_from_str("1100000101");
+        let packed = pack_bits_with_order(&bits, BitOrder::LsbFirst);
+        assert_eq!(packed, vec![0b1000_0011, 0b0000_0010]);
+        assert_ne!(packed, pack_bits(&bits));
+        assert_eq!(
+            unpack_bits_with_order(&packed, 10, BitOrder::LsbFirst),
+            bits
+        );
+
+        let mut writer = BitWriter::new(Vec::new()).with_bit_order(BitOrder::LsbFirst);
+        writer.write_bits(&bits[..3]).unwrap();
+        writer.write_packed(0b0000101, 7).unwrap();
+        writer.flush().unwrap();
+        let written = writer.into_inner();
+        assert_eq!(written, packed);
+
+        let mut reader = BitReader::new(&written[..]).with_bit_order(BitOrder::LsbFirst);
+        let read = read_all_bits(&mut reader);
+        assert_eq!(&read[..10], bits.as_slice());
+        assert!(read[10..].iter().all(|&bit| !bit));
+    }
+
+    fn read_all_bits<R: Read>(reader: &mut BitReader<R>) -> Vec<bool> {
+        let mut bits = Vec::new();
+        while let Some(bit) = reader.read_bit().unwrap() {
+            bits.push(bit);
+        }
+        bits
+    }
+
+    #[test]
+    fn test_bit_reader_msb_first() {
+        let mut reader = BitReader::new(&[0b1010_0001, 0xFF][..]);
+        assert_eq!(
+            read_all_bits(&mut reader),
+            bits_from_str("1010000111111111")
+        );
+        assert_eq!(reader.read_bit().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bit_reader_empty_input() {
+        let mut reader = BitReader::new(&[][..]);
+        assert_eq!(reader.read_bit().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bit_writer_reader_round_trip() {
+        let bits: Vec<bool> = (0..100_003).map(|i| (i * 7919) % 5 < 2).collect();
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(&bits).unwrap();
+        writer.flush().unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = BitReader::new(bytes.as_slice());
+        let mut read_back = read_all_bits(&mut reader);
+        // The writer pads the final byte; those bits are not part of the data.
+        assert_eq!(read_back.len(), bytes.len() * 8);
+        read_back.truncate(bits.len());
+        assert_eq!(read_back, bits);
+    }
+}
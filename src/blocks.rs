@@ -0,0 +1,221 @@
+//! Text split into independent blocks that are coded on separate threads.
+//!
+//! All blocks share one code, stored once in the header, and every block's
+//! bit length is listed before the payloads. A reader therefore knows where
+//! each block starts without decoding the ones before it, so decoding can
+//! be split across threads just like encoding.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::thread;
+
+use crate::bits::pack_bits;
+use crate::checksum::crc32;
+use crate::counter::{count_chars, split_chunks};
+use crate::huffman_encoder::{ByteReader, DecodeError, FormatError, HuffmanCode};
+use crate::huffman_tree::HuffmanTree;
+
+const BLOCKS_MAGIC: &[u8; 4] = b"HUFP";
+const BLOCKS_VERSION: u8 = 1;
+/// The shared code is stored as canonical lengths, which need codes of at
+/// most 64 bits.
+const MAX_CODE_LEN: u8 = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlocksError {
+    /// The file or its table is malformed.
+    Format(FormatError),
+    /// A block does not decode, or the text fails its checksum.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for BlocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlocksError::Format(e) => write!(f, "invalid block file: {}", e),
+            BlocksError::Decode(e) => write!(f, "corrupt block: {}", e),
+        }
+    }
+}
+
+impl Error for BlocksError {}
+
+impl From<FormatError> for BlocksError {
+    fn from(e: FormatError) -> Self {
+        BlocksError::Format(e)
+    }
+}
+
+impl From<DecodeError> for BlocksError {
+    fn from(e: DecodeError) -> Self {
+        BlocksError::Decode(e)
+    }
+}
+
+/// Compresses `text` as up to `threads` blocks of about equal size, each
+/// encoded on its own thread with one code built from the whole text. With
+/// one thread the output is a single block, coded on the calling thread.
+///
+/// Layout, all integers big-endian:
+///
+/// ```text
+/// magic        4 bytes   b"HUFP"
+/// version      1 byte    BLOCKS_VERSION
+/// table        code lengths, as `HuffmanCode::serialize` writes them
+/// checksum     4 bytes   CRC-32 of the text
+/// block count  8 bytes
+/// bit lengths  8 bytes per block
+/// payloads     per block: packed bits zero-padded to a whole byte
+/// ```
+pub fn write_blocks(text: &str, threads: usize) -> Vec<u8> {
+    let frequencies = count_chars(text);
+    let code = if frequencies.is_empty() {
+        HuffmanCode::new(HashMap::new())
+    } else {
+        HuffmanTree::build_length_limited(&frequencies, MAX_CODE_LEN)
+            .expect("a non-empty alphabet fits in 64-bit codes")
+    };
+
+    let chunks = split_chunks(text, threads);
+    let encode = |chunk: &str| {
+        code.encode(chunk)
+            .expect("the code covers every char of the text")
+    };
+    let blocks: Vec<Vec<bool>> = if chunks.len() <= 1 {
+        chunks.into_iter().map(encode).collect()
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| scope.spawn(move || encode(chunk)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    };
+
+    let mut out = Vec::new();
+    out.extend_from_slice(BLOCKS_MAGIC);
+    out.push(BLOCKS_VERSION);
+    out.extend_from_slice(&code.serialize());
+    out.extend_from_slice(&crc32(text.as_bytes()).to_be_bytes());
+    out.extend_from_slice(&(blocks.len() as u64).to_be_bytes());
+    for bits in &blocks {
+        out.extend_from_slice(&(bits.len() as u64).to_be_bytes());
+    }
+    for bits in &blocks {
+        out.extend_from_slice(&pack_bits(bits));
+    }
+    out
+}
+
+/// Whether `data` starts like a file written by `write_blocks`.
+pub fn is_block_file(data: &[u8]) -> bool {
+    data.starts_with(BLOCKS_MAGIC)
+}
+
+/// Decodes a file written by `write_blocks` on up to `threads` threads and
+/// checks the text against its checksum. The result does not depend on the
+/// number of threads, for writing or reading.
+pub fn read_blocks(data: &[u8], threads: usize) -> Result<String, BlocksError> {
+    let mut reader = ByteReader::new(data);
+    if reader.take(BLOCKS_MAGIC.len())? != BLOCKS_MAGIC {
+        return Err(FormatError::BadMagic.into());
+    }
+    let version = reader.read_u8()?;
+    if version != BLOCKS_VERSION {
+        return Err(FormatError::UnsupportedVersion(version).into());
+    }
+    let code = HuffmanCode::<char>::read_canonical(&mut reader)?;
+    let checksum = reader.read_u32()?;
+
+    let block_count = reader.read_u64()?;
+    let mut bit_lens = Vec::new();
+    for _ in 0..block_count {
+        bit_lens.push(usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?);
+    }
+    let mut blocks = Vec::with_capacity(bit_lens.len());
+    for bit_len in bit_lens {
+        blocks.push((reader.take(bit_len.div_ceil(8))?, bit_len));
+    }
+
+    // Each thread decodes a run of neighbouring blocks, so the results can
+    // simply be concatenated in order.
+    let per_thread = blocks.len().div_ceil(threads.max(1)).max(1);
+    let decode_run = |run: &[(&[u8], usize)]| -> Result<String, DecodeError> {
+        run.iter()
+            .map(|&(payload, bit_len)| code.decode_bytes(payload, bit_len))
+            .collect()
+    };
+    let parts: Vec<Result<String, DecodeError>> = if blocks.len() <= per_thread {
+        vec![decode_run(&blocks)]
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = blocks
+                .chunks(per_thread)
+                .map(|run| scope.spawn(move || decode_run(run)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    };
+    let text = parts.into_iter().collect::<Result<String, _>>()?;
+
+    let actual = crc32(text.as_bytes());
+    if actual != checksum {
+        return Err(DecodeError::ChecksumMismatch {
+            expected: checksum,
+            actual,
+        }
+        .into());
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_and_sequential_blocks_decode_the_same() {
+        let text = "Grüße aus Köln! 你好, 🎉 parallel blocks\n".repeat(2000);
+        let sequential = write_blocks(&text, 1);
+        let parallel = write_blocks(&text, 4);
+        assert!(is_block_file(&parallel));
+        assert_ne!(sequential, parallel);
+
+        for data in [&sequential, &parallel] {
+            for threads in [1, 2, 3, 8] {
+                assert_eq!(read_blocks(data, threads).unwrap(), text);
+            }
+        }
+        // Splitting costs an 8-byte length and at most a byte of padding
+        // per extra block.
+        assert!(parallel.len() <= sequential.len() + 3 * 9);
+    }
+
+    #[test]
+    fn test_small_and_empty_inputs() {
+        for text in ["", "a", "ab", "é"] {
+            for threads in [1, 4] {
+                let data = write_blocks(text, threads);
+                assert_eq!(read_blocks(&data, threads).unwrap(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_blocks_rejects_truncated_and_corrupt_input() {
+        let text = "some text to split into blocks\n".repeat(20);
+        let data = write_blocks(&text, 3);
+        for len in 0..data.len() {
+            assert!(read_blocks(&data[..len], 2).is_err(), "length {}", len);
+        }
+
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(
+            read_blocks(&corrupt, 2),
+            Err(BlocksError::Decode(_))
+        ));
+    }
+}
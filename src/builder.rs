@@ -0,0 +1,198 @@
+//! Step-by-step configuration of a `HuffmanCode`.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::counter::create_counter;
+use crate::error::HuffmanError;
+use crate::huffman_encoder::{EncodeOptions, HuffmanCode, TableFormat};
+use crate::huffman_tree::{BuildError, HuffmanTree};
+use crate::symbol::Symbol;
+
+/// Collects the settings for a code, then builds it in one go:
+///
+/// ```
+/// use huffman_challenge::{HuffmanCodeBuilder, TableFormat};
+///
+/// let code = HuffmanCodeBuilder::from_reader("aaaaaaaabbbbccd".as_bytes())
+///     .unwrap()
+///     .with_max_code_len(2)
+///     .with_table_format(TableFormat::CanonicalLengths)
+///     .build()
+///     .unwrap();
+/// assert_eq!(code.max_code_len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HuffmanCodeBuilder<S = char> {
+    frequencies: HashMap<S, u64>,
+    max_code_len: Option<u8>,
+    table_format: TableFormat,
+}
+
+impl HuffmanCodeBuilder<char> {
+    /// Counts the chars of UTF-8 text read from `reader`. Fails like
+    /// `create_counter` for unreadable or non-UTF-8 input.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, HuffmanError> {
+        Ok(Self::from_frequencies(create_counter(reader)?))
+    }
+}
+
+impl<S: Symbol> HuffmanCodeBuilder<S> {
+    pub fn from_frequencies(frequencies: HashMap<S, u64>) -> Self {
+        Self {
+            frequencies,
+            max_code_len: None,
+            table_format: TableFormat::default(),
+        }
+    }
+
+    /// Limits every code to at most `max_len` bits, giving the best code
+    /// within the limit. Such a code cannot be rebuilt from the frequencies,
+    /// so if the table format is left at `TableFormat::Frequencies`,
+    /// `encode_options` stores it with `TableFormat::CanonicalLengths`, or
+    /// `TableFormat::Codes` for a limit above 64 bits.
+    pub fn with_max_code_len(mut self, max_len: u8) -> Self {
+        self.max_code_len = Some(max_len);
+        self
+    }
+
+    /// Sets the table format the code will be stored with. For
+    /// `TableFormat::CanonicalLengths` the built code is canonical, so it
+    /// is exactly the code a reader rebuilds from the lengths.
+    pub fn with_table_format(mut self, table_format: TableFormat) -> Self {
+        self.table_format = table_format;
+        self
+    }
+
+    /// Default encode options, with the table format set on the builder,
+    /// or one that can store a length-limited code, see `with_max_code_len`.
+    pub fn encode_options(&self) -> EncodeOptions {
+        let table_format = match self.max_code_len {
+            Some(max_len) if self.table_format == TableFormat::Frequencies => {
+                if max_len <= 64 {
+                    TableFormat::CanonicalLengths
+                } else {
+                    TableFormat::Codes
+                }
+            }
+            _ => self.table_format,
+        };
+        EncodeOptions {
+            table_format,
+            ..EncodeOptions::default()
+        }
+    }
+
+    pub fn frequencies(&self) -> &HashMap<S, u64> {
+        &self.frequencies
+    }
+
+    /// Builds the code, failing like `HuffmanCode::from_frequencies` for an
+    /// empty input and like `HuffmanTree::build_length_limited` for a limit
+    /// too small for the number of symbols.
+    pub fn build(&self) -> Result<HuffmanCode<S>, BuildError> {
+        // Length-limited codes come out canonical already.
+        if let Some(max_len) = self.max_code_len {
+            return HuffmanTree::build_length_limited(&self.frequencies, max_len);
+        }
+        let code = HuffmanCode::from_frequencies(&self.frequencies)?;
+        if self.table_format == TableFormat::CanonicalLengths && code.max_code_len() <= 64 {
+            return Ok(HuffmanCode::canonical(&code.code_lengths()));
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::huffman_encoder::{read_container, write_compressed_container_with_options};
+    use std::io;
+
+    #[test]
+    fn test_builder_makes_length_limited_canonical_code() {
+        let text = "a".repeat(64) + &"b".repeat(32) + "ccccccccddddeef";
+        let builder = HuffmanCodeBuilder::from_reader(text.as_bytes())
+            .unwrap()
+            .with_max_code_len(3)
+            .with_table_format(TableFormat::CanonicalLengths);
+        let code = builder.build().unwrap();
+
+        assert_eq!(code.max_code_len(), 3);
+        let unlimited = HuffmanCode::from_frequencies(builder.frequencies()).unwrap();
+        assert!(unlimited.max_code_len() > 3);
+        let canonical = HuffmanCode::canonical(&code.code_lengths());
+        assert_eq!(code.codes_sorted(), canonical.codes_sorted());
+
+        let symbols: Vec<char> = text.chars().collect();
+        let container = write_compressed_container_with_options(
+            builder.frequencies(),
+            &code,
+            &symbols,
+            &builder.encode_options(),
+        )
+        .unwrap();
+        let container = read_container::<char>(&container).unwrap();
+        assert_eq!(container.decode().unwrap(), text);
+    }
+
+    #[test]
+    fn test_length_limited_code_round_trips_with_default_table_format() {
+        let text = "a".repeat(64) + &"b".repeat(32) + "ccccccccddddeef";
+        let symbols: Vec<char> = text.chars().collect();
+        for max_len in [3, 100] {
+            let builder = HuffmanCodeBuilder::from_reader(text.as_bytes())
+                .unwrap()
+                .with_max_code_len(max_len);
+            let expected = if max_len <= 64 {
+                TableFormat::CanonicalLengths
+            } else {
+                TableFormat::Codes
+            };
+            assert_eq!(builder.encode_options().table_format, expected);
+
+            let code = builder.build().unwrap();
+            let container = write_compressed_container_with_options(
+                builder.frequencies(),
+                &code,
+                &symbols,
+                &builder.encode_options(),
+            )
+            .unwrap();
+            let container = read_container::<char>(&container).unwrap();
+            assert_eq!(container.decode().unwrap(), text, "max_len {}", max_len);
+        }
+    }
+
+    #[test]
+    fn test_builder_defaults_and_errors() {
+        let frequencies = HashMap::from([('x', 3), ('y', 1), ('z', 1)]);
+        let code = HuffmanCodeBuilder::from_frequencies(frequencies.clone())
+            .build()
+            .unwrap();
+        let expected = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        assert_eq!(code.codes_sorted(), expected.codes_sorted());
+
+        assert_eq!(
+            HuffmanCodeBuilder::from_frequencies(frequencies)
+                .with_max_code_len(1)
+                .build()
+                .unwrap_err(),
+            BuildError::MaxLengthTooSmall {
+                max_len: 1,
+                symbols: 3
+            }
+        );
+        assert_eq!(
+            HuffmanCodeBuilder::from_reader(&b""[..])
+                .unwrap()
+                .build()
+                .unwrap_err(),
+            BuildError::EmptyFrequencies
+        );
+        match HuffmanCodeBuilder::from_reader(&b"\xFF"[..]) {
+            Err(HuffmanError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            other => panic!("expected an InvalidData error, got {:?}", other),
+        }
+    }
+}
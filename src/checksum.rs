@@ -0,0 +1,99 @@
+use crate::symbol::Symbol;
+
+/// Reflected CRC-32 polynomial (IEEE 802.3, as used by zip and PNG).
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = build_table();
+
+const fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32, for data that arrives in pieces.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.state = TABLE[((self.state ^ byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// CRC-32 of the symbols' serialized bytes. For chars this is the CRC-32 of
+/// the UTF-8 text, so it matches `crc32(text.as_bytes())`.
+pub fn symbols_checksum<S: Symbol, I: IntoIterator<Item = S>>(symbols: I) -> u32 {
+    let mut crc = Crc32::new();
+    let mut bytes = Vec::new();
+    for symbol in symbols {
+        bytes.clear();
+        symbol.write_bytes(&mut bytes);
+        crc.update(&bytes);
+    }
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    #[test]
+    fn test_incremental_and_symbol_checksums_match() {
+        let text = "héllo wörld 🎉";
+        let mut crc = Crc32::new();
+        for piece in text.as_bytes().chunks(3) {
+            crc.update(piece);
+        }
+        assert_eq!(crc.finish(), crc32(text.as_bytes()));
+        assert_eq!(symbols_checksum(text.chars()), crc32(text.as_bytes()));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::thread;
+
+use crate::error::HuffmanError;
+use crate::symbol::Symbols;
+
+/// Inputs smaller than this are counted on the calling thread; spawning
+/// threads costs more than it saves below roughly a megabyte.
+const PARALLEL_THRESHOLD: usize = 1024 * 1024;
+
+/// Counts how often each symbol occurs in `symbols`.
+pub fn count_symbols<S, I>(symbols: I) -> HashMap<S, u64>
+where
+    S: Eq + Hash,
+    I: IntoIterator<Item = S>,
+{
+    let mut counter = HashMap::new();
+    for symbol in symbols {
+        *counter.entry(symbol).or_insert(0) += 1;
+    }
+    counter
+}
+
+/// Adds every count of `b` to `a`, for combining counts of separate
+/// chunks or corpora.
+pub fn merge_counters<S: Eq + Hash + Clone>(a: &mut HashMap<S, u64>, b: &HashMap<S, u64>) {
+    for (symbol, &count) in b {
+        *a.entry(symbol.clone()).or_insert(0) += count;
+    }
+}
+
+/// Counts the chars of UTF-8 text read from `reader`. Fails on read errors
+/// and on invalid UTF-8, which is an `InvalidData` I/O error. Chars are
+/// counted as they are read, so memory use does not grow with the input.
+pub fn create_counter<R: Read>(reader: R) -> Result<HashMap<char, u64>, HuffmanError> {
+    let mut counter = HashMap::new();
+    for c in Symbols::new(reader) {
+        *counter.entry(c?).or_insert(0) += 1;
+    }
+    Ok(counter)
+}
+
+pub fn create_byte_counter<R: Read>(reader: R) -> Result<HashMap<u8, u64>, HuffmanError> {
+    Ok(count_bytes_buffered(reader)?.iter().collect())
+}
+
+/// Counts the bytes of the file at `path`. With the `mmap` feature a regular
+/// file is counted through a memory map; otherwise, or for pipes and other
+/// special files, it is read through a `BufReader`. Both give the same
+/// counts.
+pub fn count_file_bytes(path: &Path) -> Result<HashMap<u8, u64>, HuffmanError> {
+    let file = File::open(path)?;
+    #[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+    if let Some(map) = crate::mmap::Mmap::map(&file)? {
+        let mut counter = ByteCounter::new();
+        for &byte in map.iter() {
+            counter.count(byte);
+        }
+        return Ok(counter.iter().collect());
+    }
+    Ok(count_bytes_buffered(file)?.iter().collect())
+}
+
+fn count_bytes_buffered<R: Read>(reader: R) -> io::Result<ByteCounter> {
+    let mut reader = BufReader::new(reader);
+    let mut counter = ByteCounter::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        for &byte in chunk {
+            counter.count(byte);
+        }
+        let consumed = chunk.len();
+        reader.consume(consumed);
+    }
+    Ok(counter)
+}
+
+/// Byte frequency counter backed by a fixed array, which avoids hashing
+/// every byte when the alphabet is known to be at most 256 symbols.
+#[derive(Debug, Clone)]
+pub struct ByteCounter {
+    counts: [u64; 256],
+}
+
+impl ByteCounter {
+    pub fn new() -> Self {
+        Self { counts: [0; 256] }
+    }
+
+    pub fn count(&mut self, byte: u8) {
+        self.counts[byte as usize] += 1;
+    }
+
+    /// Iterates over the bytes that were seen at least once, in byte order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(byte, &count)| (byte as u8, count))
+    }
+}
+
+impl Default for ByteCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts the chars of `text`, splitting the work across threads when the
+/// input is large enough for it to pay off.
+pub fn count_chars(text: &str) -> HashMap<char, u64> {
+    if text.len() < PARALLEL_THRESHOLD {
+        return count_chars_sequential(text);
+    }
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    create_counter_parallel(text, threads)
+}
+
+fn count_chars_sequential(text: &str) -> HashMap<char, u64> {
+    count_symbols(text.chars())
+}
+
+/// Splits `text` into `threads` chunks on char boundaries, counts each chunk
+/// on its own thread and merges the partial counts.
+pub fn create_counter_parallel(text: &str, threads: usize) -> HashMap<char, u64> {
+    let chunks = split_chunks(text, threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || count_chars_sequential(chunk)))
+            .collect();
+
+        let mut counter = HashMap::new();
+        for handle in handles {
+            merge_counters(&mut counter, &handle.join().unwrap());
+        }
+        counter
+    })
+}
+
+/// Splits `text` into at most `parts` chunks of about equal length, moving
+/// each split forward to the next char boundary. Empty text has no chunks.
+pub(crate) fn split_chunks(text: &str, parts: usize) -> Vec<&str> {
+    let parts = parts.max(1);
+    let chunk_len = text.len().div_ceil(parts).max(1);
+
+    let mut chunks = Vec::with_capacity(parts);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut split = chunk_len.min(rest.len());
+        while !rest.is_char_boundary(split) {
+            split += 1;
+        }
+        let (chunk, tail) = rest.split_at(split);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_create_counter() {
+        let input_data = "hello world";
+        let fake_file = Cursor::new(input_data.as_bytes().to_vec());
+
+        let counter = create_counter(fake_file).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert('h', 1);
+        expected.insert('e', 1);
+        expected.insert('l', 3);
+        expected.insert('o', 2);
+        expected.insert(' ', 1);
+        expected.insert('w', 1);
+        expected.insert('r', 1);
+        expected.insert('d', 1);
+
+        assert_eq!(counter, expected);
+    }
+
+    #[test]
+    fn test_create_counter_reports_invalid_utf8() {
+        assert_eq!(
+            create_counter(Cursor::new("héllo")).unwrap(),
+            count_chars("héllo")
+        );
+        match create_counter(Cursor::new(b"ab\xFFc".to_vec())) {
+            Err(HuffmanError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            other => panic!("expected an InvalidData error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_create_counter_counts_newlines() {
+        let fake_file = Cursor::new(b"a\nb\n".to_vec());
+
+        let counter = create_counter(fake_file).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert('a', 1);
+        expected.insert('b', 1);
+        expected.insert('\n', 2);
+
+        assert_eq!(counter, expected);
+    }
+
+    #[test]
+    fn test_create_counter_keeps_crlf() {
+        let counter = create_counter(Cursor::new(b"a\r\nb\r\n".to_vec())).unwrap();
+
+        let expected = HashMap::from([('a', 1), ('b', 1), ('\r', 2), ('\n', 2)]);
+        assert_eq!(counter, expected);
+        assert_eq!(count_chars("a\r\nb\r\n"), expected);
+    }
+
+    #[test]
+    fn test_count_symbols_chars_and_bytes() {
+        let chars = count_symbols("abcab".chars());
+        assert_eq!(chars, HashMap::from([('a', 2), ('b', 2), ('c', 1)]));
+
+        let bytes = count_symbols([0x00u8, 0xFF, 0x00, 0x00]);
+        assert_eq!(bytes, HashMap::from([(0x00, 3), (0xFF, 1)]));
+        assert_eq!(
+            bytes,
+            create_byte_counter(Cursor::new([0x00, 0xFF, 0x00, 0x00])).unwrap()
+        );
+
+        assert!(count_symbols(std::iter::empty::<char>()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_counters_sums_overlapping_maps() {
+        let mut a = HashMap::from([('a', 2), ('b', 1)]);
+        let b = HashMap::from([('b', 4), ('c', 3)]);
+        merge_counters(&mut a, &b);
+        assert_eq!(a, HashMap::from([('a', 2), ('b', 5), ('c', 3)]));
+
+        merge_counters(&mut a, &HashMap::new());
+        assert_eq!(a.len(), 3);
+
+        let mut merged = count_chars("hello ");
+        merge_counters(&mut merged, &count_chars("world"));
+        assert_eq!(merged, count_chars("hello world"));
+    }
+
+    #[test]
+    fn test_create_byte_counter() {
+        let fake_file = Cursor::new(vec![0x00, 0xFF, b'a', 0x00, b'\n', 0xFF, 0x00]);
+
+        let counter = create_byte_counter(fake_file).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert(0x00, 3);
+        expected.insert(0xFF, 2);
+        expected.insert(b'a', 1);
+        expected.insert(b'\n', 1);
+
+        assert_eq!(counter, expected);
+    }
+
+    #[test]
+    fn test_parallel_counter_matches_sequential() {
+        let text = "Grüße aus Köln! 你好, 🎉 party\n".repeat(5000);
+        let sequential = create_counter(Cursor::new(text.as_bytes())).unwrap();
+        for threads in [1, 2, 3, 7, 16] {
+            assert_eq!(create_counter_parallel(&text, threads), sequential);
+        }
+        assert_eq!(count_chars(&text), sequential);
+    }
+
+    #[test]
+    fn test_parallel_counter_small_inputs() {
+        assert!(create_counter_parallel("", 4).is_empty());
+        let counter = create_counter_parallel("é", 8);
+        assert_eq!(counter.len(), 1);
+        assert_eq!(counter[&'é'], 1);
+    }
+
+    #[test]
+    fn test_byte_counter_matches_hash_map_counting() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| (i * i % 251) as u8).collect();
+
+        let mut expected = HashMap::new();
+        for &byte in &data {
+            *expected.entry(byte).or_insert(0) += 1;
+        }
+
+        let mut counter = ByteCounter::new();
+        for &byte in &data {
+            counter.count(byte);
+        }
+        let counted: HashMap<u8, u64> = counter.iter().collect();
+        assert_eq!(counted, expected);
+        assert_eq!(create_byte_counter(Cursor::new(&data)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_file_bytes_matches_buffered_counting() {
+        let path = std::env::temp_dir().join(format!("counter-{}.bin", std::process::id()));
+        let data: Vec<u8> = (0..300_000u64).map(|i| (i * 31 % 256) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let counts = count_file_bytes(&path);
+        std::fs::write(&path, b"").unwrap();
+        let empty = count_file_bytes(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            counts.unwrap(),
+            create_byte_counter(Cursor::new(&data)).unwrap()
+        );
+        assert!(empty.unwrap().is_empty());
+        assert!(count_file_bytes(Path::new("does/not/exist")).is_err());
+    }
+
+    #[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+    #[test]
+    fn test_mapped_file_matches_buffered_counts() {
+        let path = std::env::temp_dir().join(format!("mapped-{}.txt", std::process::id()));
+        let text = "Grüße aus Köln! 🎉\n".repeat(10_000);
+        std::fs::write(&path, &text).unwrap();
+        let file = File::open(&path).unwrap();
+        let map = crate::mmap::Mmap::map(&file)
+            .unwrap()
+            .expect("a regular file maps");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&map[..], text.as_bytes());
+        let mapped = count_symbols(map.iter().copied());
+        assert_eq!(
+            mapped,
+            create_byte_counter(Cursor::new(text.as_bytes())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_byte_counter_iterates_nonzero_in_order() {
+        let mut counter = ByteCounter::new();
+        for byte in [0xFF, 0x00, 0x10, 0xFF] {
+            counter.count(byte);
+        }
+        let entries: Vec<_> = counter.iter().collect();
+        assert_eq!(entries, vec![(0x00, 1), (0x10, 1), (0xFF, 2)]);
+    }
+}
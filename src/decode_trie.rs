@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::huffman_encoder::{canonical_table, check_canonical_lengths, DecodeError, FormatError};
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone)]
+struct DecodeNode<S> {
+    children: [Option<usize>; 2],
+    symbol: Option<S>,
+}
+
+impl<S> Default for DecodeNode<S> {
+    fn default() -> Self {
+        Self {
+            children: [None, None],
+            symbol: None,
+        }
+    }
+}
+
+/// Outcome of feeding one bit to a `DecodeTrie`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step<S> {
+    /// The bit completed a codeword; the trie is back at its root.
+    Symbol(S),
+    /// The bit is part of a codeword that is not finished yet.
+    Continue,
+}
+
+/// Binary trie mapping codewords back to symbols, decoding one bit at a
+/// time. The trie remembers how far into the current codeword it is, so
+/// input can be fed to it in pieces of any size.
+#[derive(Debug, Clone)]
+pub struct DecodeTrie<S = char> {
+    nodes: Vec<DecodeNode<S>>,
+    current: usize,
+}
+
+impl<S: Symbol> DecodeTrie<S> {
+    /// Builds the trie for the codewords of `encoding_table`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table is not a prefix code, see `try_new`.
+    pub fn new(encoding_table: &HashMap<S, Vec<bool>>) -> Self {
+        Self::try_new(encoding_table).expect("encoding table must be a prefix code")
+    }
+
+    /// Builds the trie for the codewords of `encoding_table`, failing with
+    /// `FormatError::NotPrefixCode` if a code is empty, passes through
+    /// another symbol's leaf, or ends on a node that already has a symbol or
+    /// children.
+    pub fn try_new(encoding_table: &HashMap<S, Vec<bool>>) -> Result<Self, FormatError> {
+        let mut nodes = vec![DecodeNode::default()];
+        for (&c, code) in encoding_table {
+            if code.is_empty() {
+                return Err(FormatError::NotPrefixCode);
+            }
+            let mut current = 0;
+            for &bit in code {
+                if nodes[current].symbol.is_some() {
+                    return Err(FormatError::NotPrefixCode);
+                }
+                current = match nodes[current].children[bit as usize] {
+                    Some(next) => next,
+                    None => {
+                        nodes.push(DecodeNode::default());
+                        let next = nodes.len() - 1;
+                        nodes[current].children[bit as usize] = Some(next);
+                        next
+                    }
+                };
+            }
+            if nodes[current].symbol.is_some() || nodes[current].children != [None, None] {
+                return Err(FormatError::NotPrefixCode);
+            }
+            nodes[current].symbol = Some(c);
+        }
+        Ok(Self { nodes, current: 0 })
+    }
+
+    /// Builds the trie for the canonical code with the given code lengths,
+    /// the decoding side of `HuffmanCode::canonical`, without building the
+    /// encoding table first. Symbols with a length of zero are left out.
+    /// Lengths that cannot form a prefix code, or longer than 64 bits, are
+    /// rejected with `FormatError::NotPrefixCode`.
+    pub fn from_lengths(lengths: &HashMap<S, u8>) -> Result<Self, FormatError> {
+        check_canonical_lengths(lengths)?;
+        Self::try_new(&canonical_table(lengths))
+    }
+
+    /// Follows `bit` from the current position. A bit with no matching
+    /// branch yields `DecodeError::InvalidBitstream` and leaves the position
+    /// unchanged.
+    pub fn step(&mut self, bit: bool) -> Result<Step<S>, DecodeError> {
+        let next =
+            self.nodes[self.current].children[bit as usize].ok_or(DecodeError::InvalidBitstream)?;
+        match self.nodes[next].symbol {
+            Some(c) => {
+                self.current = 0;
+                Ok(Step::Symbol(c))
+            }
+            None => {
+                self.current = next;
+                Ok(Step::Continue)
+            }
+        }
+    }
+
+    /// Whether the trie sits between codewords rather than inside one.
+    pub fn at_root(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Drops any partially read codeword.
+    pub fn reset(&mut self) {
+        self.current = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_chars;
+    use crate::huffman_encoder::HuffmanCode;
+
+    fn bits(code: &str) -> Vec<bool> {
+        code.chars().map(|c| c == '1').collect()
+    }
+
+    fn sample_trie() -> DecodeTrie {
+        let table = HashMap::from([('a', bits("0")), ('b', bits("10")), ('c', bits("11"))]);
+        DecodeTrie::new(&table)
+    }
+
+    #[test]
+    fn test_step_through_known_code() {
+        let mut trie = sample_trie();
+        assert_eq!(trie.step(false), Ok(Step::Symbol('a')));
+        assert!(trie.at_root());
+        assert_eq!(trie.step(true), Ok(Step::Continue));
+        assert!(!trie.at_root());
+        assert_eq!(trie.step(false), Ok(Step::Symbol('b')));
+        assert_eq!(trie.step(true), Ok(Step::Continue));
+        assert_eq!(trie.step(true), Ok(Step::Symbol('c')));
+        assert!(trie.at_root());
+    }
+
+    #[test]
+    fn test_step_rejects_bit_outside_the_code() {
+        let table = HashMap::from([('a', bits("0")), ('b', bits("10"))]);
+        let mut trie = DecodeTrie::new(&table);
+        assert_eq!(trie.step(true), Ok(Step::Continue));
+        assert_eq!(trie.step(true), Err(DecodeError::InvalidBitstream));
+        assert!(!trie.at_root());
+        trie.reset();
+        assert_eq!(trie.step(false), Ok(Step::Symbol('a')));
+    }
+
+    #[test]
+    fn test_try_new_rejects_non_prefix_tables() {
+        let prefix_of_other = HashMap::from([('a', bits("1")), ('b', bits("10"))]);
+        let other_is_prefix = HashMap::from([('a', bits("10")), ('b', bits("1"))]);
+        let duplicate = HashMap::from([('a', bits("01")), ('b', bits("01"))]);
+        let empty = HashMap::from([('a', bits(""))]);
+        for table in [prefix_of_other, other_is_prefix, duplicate, empty] {
+            assert_eq!(
+                DecodeTrie::try_new(&table).unwrap_err(),
+                FormatError::NotPrefixCode
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_lengths_decodes_canonical_code() {
+        let text = "canonical codes need only their lengths";
+        let code = HuffmanCode::from_frequencies(&count_chars(text)).unwrap();
+        let canonical = HuffmanCode::canonical(&code.code_lengths());
+        let bits = canonical.encode(text).unwrap();
+
+        let mut trie = DecodeTrie::from_lengths(&code.code_lengths()).unwrap();
+        let mut decoded = String::new();
+        for bit in bits {
+            if let Step::Symbol(c) = trie.step(bit).unwrap() {
+                decoded.push(c);
+            }
+        }
+        assert!(trie.at_root());
+        assert_eq!(decoded, text);
+
+        assert_eq!(
+            DecodeTrie::from_lengths(&HashMap::from([('a', 1), ('b', 1), ('c', 1)])).unwrap_err(),
+            FormatError::NotPrefixCode
+        );
+        assert_eq!(
+            DecodeTrie::from_lengths(&HashMap::from([('a', 65), ('b', 1)])).unwrap_err(),
+            FormatError::NotPrefixCode
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! One error type for callers that do not care which stage failed.
+//!
+//! Every module keeps its own error enum, which says exactly what can go
+//! wrong there. `HuffmanError` collects them, so code that reads, builds,
+//! encodes and decodes in one go can use `?` throughout. The entry points
+//! that read input themselves, such as `create_counter`,
+//! `count_file_bytes` and `HuffmanCode::from_reader`, return it directly,
+//! since they can fail on I/O as well as on coding.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::huffman_encoder::{DecodeError, EncodeError, FormatError};
+use crate::huffman_tree::BuildError;
+use crate::symbol::Symbol;
+
+#[derive(Debug)]
+pub enum HuffmanError {
+    Io(io::Error),
+    /// There were no symbols to build a code from.
+    EmptyInput,
+    /// The bits do not follow a path of the code.
+    InvalidBitstream,
+    /// The decoded data does not match the checksum stored with it.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// Decoding would produce more than the allowed number of bytes.
+    OutputLimitExceeded {
+        limit: u64,
+    },
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// Any other malformed container or table.
+    Format(FormatError),
+    /// A symbol to encode is not in the code, shown as its `Debug` form.
+    UnknownSymbol(String),
+    /// A code could not be built within a length limit.
+    Build(BuildError),
+}
+
+impl fmt::Display for HuffmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HuffmanError::Io(e) => write!(f, "I/O error: {}", e),
+            HuffmanError::EmptyInput => write!(f, "input is empty, there is nothing to code"),
+            HuffmanError::InvalidBitstream => DecodeError::InvalidBitstream.fmt(f),
+            HuffmanError::ChecksumMismatch { expected, actual } => DecodeError::ChecksumMismatch {
+                expected: *expected,
+                actual: *actual,
+            }
+            .fmt(f),
+            HuffmanError::OutputLimitExceeded { limit } => {
+                DecodeError::OutputLimitExceeded { limit: *limit }.fmt(f)
+            }
+            HuffmanError::BadMagic => FormatError::BadMagic.fmt(f),
+            HuffmanError::UnsupportedVersion(v) => FormatError::UnsupportedVersion(*v).fmt(f),
+            HuffmanError::Format(e) => e.fmt(f),
+            HuffmanError::UnknownSymbol(symbol) => {
+                write!(f, "symbol {} is not in the encoding table", symbol)
+            }
+            HuffmanError::Build(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for HuffmanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HuffmanError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HuffmanError {
+    fn from(e: io::Error) -> Self {
+        HuffmanError::Io(e)
+    }
+}
+
+impl From<FormatError> for HuffmanError {
+    fn from(e: FormatError) -> Self {
+        match e {
+            FormatError::BadMagic => HuffmanError::BadMagic,
+            FormatError::UnsupportedVersion(v) => HuffmanError::UnsupportedVersion(v),
+            e => HuffmanError::Format(e),
+        }
+    }
+}
+
+impl From<DecodeError> for HuffmanError {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::InvalidBitstream => HuffmanError::InvalidBitstream,
+            DecodeError::ChecksumMismatch { expected, actual } => {
+                HuffmanError::ChecksumMismatch { expected, actual }
+            }
+            DecodeError::OutputLimitExceeded { limit } => {
+                HuffmanError::OutputLimitExceeded { limit }
+            }
+        }
+    }
+}
+
+impl<S: Symbol> From<EncodeError<S>> for HuffmanError {
+    fn from(e: EncodeError<S>) -> Self {
+        match e {
+            EncodeError::UnknownSymbol(symbol) => {
+                HuffmanError::UnknownSymbol(format!("{:?}", symbol))
+            }
+        }
+    }
+}
+
+impl From<BuildError> for HuffmanError {
+    fn from(e: BuildError) -> Self {
+        match e {
+            BuildError::EmptyFrequencies => HuffmanError::EmptyInput,
+            e => HuffmanError::Build(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_variant_formats() {
+        let cases = [
+            (
+                HuffmanError::Io(io::Error::new(io::ErrorKind::NotFound, "gone")),
+                "I/O error: gone",
+            ),
+            (
+                HuffmanError::EmptyInput,
+                "input is empty, there is nothing to code",
+            ),
+            (
+                HuffmanError::InvalidBitstream,
+                "bitstream is not valid for this code",
+            ),
+            (
+                HuffmanError::ChecksumMismatch {
+                    expected: 0xdeadbeef,
+                    actual: 1,
+                },
+                "checksum mismatch: expected deadbeef, decoded data has 00000001",
+            ),
+            (
+                HuffmanError::OutputLimitExceeded { limit: 10 },
+                "decoded output exceeds the limit of 10 bytes",
+            ),
+            (
+                HuffmanError::BadMagic,
+                "not a huffman container (bad magic bytes)",
+            ),
+            (
+                HuffmanError::UnsupportedVersion(99),
+                "unsupported format version 99",
+            ),
+            (
+                HuffmanError::Format(FormatError::Truncated),
+                "container is truncated",
+            ),
+            (
+                HuffmanError::UnknownSymbol("'x'".to_string()),
+                "symbol 'x' is not in the encoding table",
+            ),
+            (
+                HuffmanError::Build(BuildError::MaxLengthTooSmall {
+                    max_len: 1,
+                    symbols: 3,
+                }),
+                "3 symbols cannot be coded with codes of at most 1 bits",
+            ),
+        ];
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_module_errors_convert() {
+        assert!(matches!(
+            HuffmanError::from(FormatError::BadMagic),
+            HuffmanError::BadMagic
+        ));
+        assert!(matches!(
+            HuffmanError::from(FormatError::UnsupportedVersion(3)),
+            HuffmanError::UnsupportedVersion(3)
+        ));
+        assert!(matches!(
+            HuffmanError::from(FormatError::EmptyTable),
+            HuffmanError::Format(FormatError::EmptyTable)
+        ));
+        assert!(matches!(
+            HuffmanError::from(DecodeError::ChecksumMismatch {
+                expected: 1,
+                actual: 2
+            }),
+            HuffmanError::ChecksumMismatch {
+                expected: 1,
+                actual: 2
+            }
+        ));
+        assert!(matches!(
+            HuffmanError::from(BuildError::EmptyFrequencies),
+            HuffmanError::EmptyInput
+        ));
+        let unknown = HuffmanError::from(EncodeError::UnknownSymbol(0xFFu8));
+        assert_eq!(
+            unknown.to_string(),
+            "symbol 255 is not in the encoding table"
+        );
+
+        let io = HuffmanError::from(io::Error::new(io::ErrorKind::InvalidData, "bad"));
+        assert!(io.source().is_some());
+        assert!(HuffmanError::EmptyInput.source().is_none());
+    }
+}
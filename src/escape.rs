@@ -0,0 +1,155 @@
+//! Codes that can encode any char, not just those of their model.
+//!
+//! The tree gets one extra leaf, the escape codeword. A char missing from
+//! the model is written as the escape codeword followed by its UTF-8
+//! bytes, 8 bits each, so a fixed or sampled model never fails to encode.
+
+use std::collections::HashMap;
+
+use crate::decode_trie::{DecodeTrie, Step};
+use crate::huffman_encoder::{DecodeError, FormatError};
+use crate::huffman_tree::HuffmanTree;
+use crate::symbol::Symbol;
+
+/// Leaf used for the escape codeword. U+FFFF is a noncharacter, reserved
+/// for internal use, so text is not expected to contain it; if it does, it
+/// is escaped like any other unknown char.
+pub const ESCAPE: char = '\u{FFFF}';
+
+/// A char code with an escape codeword for chars outside its model.
+#[derive(Debug, Clone)]
+pub struct EscapeCode {
+    encoding_table: HashMap<char, Vec<bool>>,
+    decode_trie: DecodeTrie<char>,
+}
+
+impl EscapeCode {
+    /// Builds the Huffman code for `frequencies` plus the escape codeword,
+    /// which is given a count of 1 so that it takes one of the longest
+    /// codes. An empty model is allowed: every char is then escaped.
+    pub fn from_frequencies(frequencies: &HashMap<char, u64>) -> Self {
+        let mut frequencies = frequencies.clone();
+        let escape = frequencies.entry(ESCAPE).or_insert(0);
+        *escape = escape.saturating_add(1);
+        let tree = HuffmanTree::build_tree(&frequencies)
+            .expect("the escape leaf makes the frequencies non-empty");
+        let encoding_table = tree.build_encoding_table();
+        let decode_trie = DecodeTrie::new(&encoding_table);
+        Self {
+            encoding_table,
+            decode_trie,
+        }
+    }
+
+    /// The escape codeword.
+    pub fn escape_code(&self) -> &[bool] {
+        &self.encoding_table[&ESCAPE]
+    }
+
+    /// Encodes `text`, escaping every char the model does not contain.
+    /// Unlike `HuffmanCode::encode` this cannot fail.
+    pub fn encode(&self, text: &str) -> Vec<bool> {
+        let mut bits = Vec::new();
+        let mut utf8 = Vec::new();
+        for c in text.chars() {
+            match self.encoding_table.get(&c).filter(|_| c != ESCAPE) {
+                Some(code) => bits.extend_from_slice(code),
+                None => {
+                    bits.extend_from_slice(self.escape_code());
+                    utf8.clear();
+                    c.write_bytes(&mut utf8);
+                    for byte in &utf8 {
+                        bits.extend((0..8).rev().map(|i| (byte >> i) & 1 == 1));
+                    }
+                }
+            }
+        }
+        bits
+    }
+
+    /// Decodes bits written by `encode`. Bits that do not follow the code,
+    /// or escaped bytes that are not a whole UTF-8 char, are an invalid
+    /// bitstream.
+    pub fn decode(&self, bits: &[bool]) -> Result<String, DecodeError> {
+        let mut trie = self.decode_trie.clone();
+        let mut text = String::new();
+        let mut bits = bits.iter().copied();
+        while let Some(bit) = bits.next() {
+            match trie.step(bit)? {
+                Step::Symbol(ESCAPE) => text.push(read_escaped(&mut bits)?),
+                Step::Symbol(c) => text.push(c),
+                Step::Continue => {}
+            }
+        }
+        if !trie.at_root() {
+            return Err(DecodeError::InvalidBitstream);
+        }
+        Ok(text)
+    }
+}
+
+/// Reads the UTF-8 bytes of one escaped char, 8 bits at a time.
+fn read_escaped<I: Iterator<Item = bool>>(bits: &mut I) -> Result<char, DecodeError> {
+    let mut utf8 = Vec::with_capacity(4);
+    loop {
+        let mut byte = 0u8;
+        for _ in 0..8 {
+            let bit = bits.next().ok_or(DecodeError::InvalidBitstream)?;
+            byte = byte << 1 | bit as u8;
+        }
+        utf8.push(byte);
+        match char::read_bytes(&utf8) {
+            Ok((c, _)) => return Ok(c),
+            Err(FormatError::Truncated) => {}
+            Err(_) => return Err(DecodeError::InvalidBitstream),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_chars;
+
+    #[test]
+    fn test_escape_round_trips_chars_outside_the_model() {
+        let code = EscapeCode::from_frequencies(&count_chars("hello world"));
+        for text in ["hello world", "hello wörld! 🎉", "QZ", "", "\u{FFFF}lo"] {
+            let bits = code.encode(text);
+            assert_eq!(code.decode(&bits).unwrap(), text);
+        }
+
+        // Known chars keep their short codes; an unknown one costs the
+        // escape codeword plus 8 bits per UTF-8 byte.
+        let escape_len = code.escape_code().len();
+        let base = code.encode("hello").len();
+        assert_eq!(code.encode("hello€").len(), base + escape_len + 24);
+    }
+
+    #[test]
+    fn test_empty_model_escapes_everything() {
+        let code = EscapeCode::from_frequencies(&HashMap::new());
+        let bits = code.encode("aé");
+        assert_eq!(bits.len(), 2 * code.escape_code().len() + 3 * 8);
+        assert_eq!(code.decode(&bits).unwrap(), "aé");
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_escapes() {
+        let code = EscapeCode::from_frequencies(&count_chars("abc"));
+        let bits = code.encode("🎉");
+        for len in 1..bits.len() {
+            assert_eq!(
+                code.decode(&bits[..len]),
+                Err(DecodeError::InvalidBitstream),
+                "length {}",
+                len
+            );
+        }
+
+        // An escape followed by a continuation byte is not a char.
+        let mut bits = code.escape_code().to_vec();
+        bits.extend([true, false, false, false, false, false, false, false]);
+        assert_eq!(code.decode(&bits), Err(DecodeError::InvalidBitstream));
+    }
+}
@@ -0,0 +1,3167 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::adaptive::AdaptiveHuffman;
+use crate::bits::{
+    pack_bits, pack_bits_with_order, unpack_bits, unpack_bits_with_order, BitOrder, BitReader,
+    BitWriter,
+};
+use crate::checksum::{crc32, symbols_checksum};
+use crate::counter::{count_symbols, create_counter};
+use crate::decode_trie::{DecodeTrie, Step};
+use crate::error::HuffmanError;
+use crate::huffman_tree::{BuildError, HuffmanTree};
+use crate::symbol::{Symbol, Symbols};
+use crate::transform::Transform;
+
+const FORMAT_MAGIC: &[u8; 4] = b"HUFF";
+const FORMAT_VERSION: u8 = 11;
+/// Bytes before the table or stored data: magic, version, kind and block
+/// type.
+const CONTAINER_PREFIX_LEN: usize = FORMAT_MAGIC.len() + 3;
+const STREAM_CHUNK_SIZE: usize = 8 * 1024;
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Settings for the streaming encoder, `write_compressed_container_with_options`
+/// and `compress_symbols`. `block_size` affects memory use and throughput
+/// only; `bit_order` changes how the bits are laid out in bytes,
+/// `table_format` how the code is stored in a container, `sample_bytes`
+/// what the code is built from and `transform` what is coded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Number of input bytes read per call, which is also the number of
+    /// output bytes buffered before they are written. Must be non-zero.
+    pub block_size: usize,
+    /// Order in which code bits fill each output byte. Containers record
+    /// it; raw streams have to be decoded with the same order.
+    pub bit_order: BitOrder,
+    /// How a container's header describes the code. Containers record it;
+    /// raw streams carry no table.
+    pub table_format: TableFormat,
+    /// Build the code from only the first this many bytes of the input
+    /// instead of all of it, saving a full counting pass over huge inputs.
+    /// Only `compress_symbols` counts; the other functions take a code.
+    pub sample_bytes: Option<u64>,
+    /// Transform the serialized symbols before coding them, and wrap the
+    /// coded bytes in a `BlockType::Transformed` container that records how
+    /// to undo it. Only `compress_symbols` transforms.
+    pub transform: Option<Transform>,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            bit_order: BitOrder::MsbFirst,
+            table_format: TableFormat::Frequencies,
+            sample_bytes: None,
+            transform: None,
+        }
+    }
+}
+
+/// Settings for the `Container` decode methods. The default has no limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Fail with `DecodeError::OutputLimitExceeded` rather than decode more
+    /// than this many bytes of serialized symbols. A small container can
+    /// claim gigabytes of output, so untrusted input should set a limit;
+    /// decoding stops early instead of filling memory first.
+    pub max_output_bytes: Option<u64>,
+}
+
+/// How the code of a Huffman container is stored in its header, stored as
+/// one byte after the bit order. The formats trade header size against the
+/// work needed to rebuild the code when reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableFormat {
+    /// Each symbol's count (8 bytes), as `serialize_frequencies` writes it.
+    /// The reader rebuilds the tree from the counts.
+    #[default]
+    Frequencies = 0,
+    /// Each symbol's code bits, as `HuffmanCode::serialize_codes` writes
+    /// them. The reader uses them as they are.
+    Codes = 1,
+    /// Each symbol's code length (1 byte), as `HuffmanCode::serialize`
+    /// writes it. The smallest header; the payload is coded with the
+    /// canonical code for those lengths.
+    CanonicalLengths = 2,
+}
+
+impl TryFrom<u8> for TableFormat {
+    type Error = FormatError;
+
+    fn try_from(byte: u8) -> Result<Self, FormatError> {
+        match byte {
+            0 => Ok(TableFormat::Frequencies),
+            1 => Ok(TableFormat::Codes),
+            2 => Ok(TableFormat::CanonicalLengths),
+            _ => Err(FormatError::UnknownTableFormat(byte)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncodeError<S = char> {
+    UnknownSymbol(S),
+}
+
+impl<S: Symbol> fmt::Display for EncodeError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::UnknownSymbol(c) => {
+                write!(f, "symbol {:?} is not in the encoding table", c)
+            }
+        }
+    }
+}
+
+impl<S: Symbol> Error for EncodeError<S> {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bits do not follow a path of the code, or stop partway through a
+    /// codeword.
+    InvalidBitstream,
+    /// The decoded data does not match the checksum stored with it.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// Decoding stopped because the output would exceed
+    /// `DecodeOptions::max_output_bytes`.
+    OutputLimitExceeded { limit: u64 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidBitstream => write!(f, "bitstream is not valid for this code"),
+            DecodeError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, decoded data has {:08x}",
+                expected, actual
+            ),
+            DecodeError::OutputLimitExceeded { limit } => {
+                write!(f, "decoded output exceeds the limit of {} bytes", limit)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    SymbolKindMismatch(u8),
+    Truncated,
+    InvalidSymbol,
+    EmptyTable,
+    /// The table has an empty code, or a code that is a prefix of another,
+    /// so it cannot be decoded unambiguously.
+    NotPrefixCode,
+    /// The frequencies add up to more than a count can hold.
+    FrequencyOverflow,
+    UnknownBlockType(u8),
+    UnknownBitOrder(u8),
+    UnknownTableFormat(u8),
+    UnknownTransform(u8),
+    /// A transformed container holds another transformed container.
+    NestedTransform,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "not a huffman container (bad magic bytes)"),
+            FormatError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            FormatError::SymbolKindMismatch(k) => {
+                write!(f, "container holds symbols of unexpected kind {}", k)
+            }
+            FormatError::Truncated => write!(f, "container is truncated"),
+            FormatError::InvalidSymbol => write!(f, "table contains an invalid symbol"),
+            FormatError::EmptyTable => write!(f, "container has an empty frequency table"),
+            FormatError::FrequencyOverflow => write!(f, "frequency table total overflows"),
+            FormatError::UnknownBlockType(t) => write!(f, "unknown block type {}", t),
+            FormatError::UnknownBitOrder(order) => write!(f, "unknown bit order {}", order),
+            FormatError::UnknownTableFormat(format) => {
+                write!(f, "unknown table format {}", format)
+            }
+            FormatError::UnknownTransform(t) => write!(f, "unknown transform {}", t),
+            FormatError::NestedTransform => {
+                write!(f, "transformed container nests another transform")
+            }
+            FormatError::NotPrefixCode => {
+                write!(
+                    f,
+                    "code table is ambiguous: one code is a prefix of another"
+                )
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Cursor over a byte slice for parsing containers; every read past the
+/// end is `FormatError::Truncated`.
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], FormatError> {
+        let end = self.pos.checked_add(n).ok_or(FormatError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(FormatError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, FormatError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, FormatError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_symbol<S: Symbol>(&mut self) -> Result<S, FormatError> {
+        let (symbol, width) = S::read_bytes(&self.data[self.pos..])?;
+        self.pos += width;
+        Ok(symbol)
+    }
+}
+
+/// A codeword as stored in the encoding table. Codes of up to 64 bits,
+/// which is every code a tree over a realistic input produces, are kept
+/// as the low `len` bits of an integer so encoding needs no allocation;
+/// longer ones fall back to a bit vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PackedCode {
+    Short { bits: u64, len: u8 },
+    Long(Vec<bool>),
+}
+
+impl PackedCode {
+    fn from_bits(code: &[bool]) -> Self {
+        if code.len() > 64 {
+            return PackedCode::Long(code.to_vec());
+        }
+        let bits = code.iter().fold(0u64, |acc, &bit| (acc << 1) | bit as u64);
+        PackedCode::Short {
+            bits,
+            len: code.len() as u8,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            PackedCode::Short { len, .. } => *len as usize,
+            PackedCode::Long(code) => code.len(),
+        }
+    }
+
+    fn to_bits(&self) -> Vec<bool> {
+        let mut out = Vec::with_capacity(self.len());
+        self.append_to(&mut out);
+        out
+    }
+
+    fn append_to(&self, out: &mut Vec<bool>) {
+        match self {
+            PackedCode::Short { bits, len } => {
+                out.extend((0..*len).rev().map(|i| (bits >> i) & 1 == 1));
+            }
+            PackedCode::Long(code) => out.extend_from_slice(code),
+        }
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        match self {
+            PackedCode::Short { bits, len } => writer.write_packed(*bits, *len),
+            PackedCode::Long(code) => writer.write_bits(code),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HuffmanCode<S = char> {
+    encoding_table: HashMap<S, PackedCode>,
+    decode_trie: DecodeTrie<S>,
+}
+
+impl<S: Symbol> HuffmanCode<S> {
+    /// Wraps a prefix-free encoding table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table is not a prefix code; use `try_new` for tables
+    /// from untrusted sources.
+    pub fn new(encoding_table: HashMap<S, Vec<bool>>) -> Self {
+        Self::try_new(encoding_table).expect("encoding table must be a prefix code")
+    }
+
+    /// Wraps `encoding_table`, failing with `FormatError::NotPrefixCode` if
+    /// a code is empty or a prefix of another one.
+    pub fn try_new(encoding_table: HashMap<S, Vec<bool>>) -> Result<Self, FormatError> {
+        let decode_trie = DecodeTrie::try_new(&encoding_table)?;
+        let encoding_table = encoding_table
+            .iter()
+            .map(|(&c, code)| (c, PackedCode::from_bits(code)))
+            .collect();
+        Ok(Self {
+            encoding_table,
+            decode_trie,
+        })
+    }
+
+    /// Builds the canonical code for the given code lengths: symbols are
+    /// sorted by `(length, symbol)` and receive consecutive code values,
+    /// shifted left whenever the length grows. Only the lengths are needed
+    /// to reproduce the code, which keeps serialized tables small. Symbols
+    /// with a length of zero are left out.
+    pub fn canonical(lengths: &HashMap<S, u8>) -> Self {
+        Self::new(canonical_table(lengths))
+    }
+
+    /// Builds the Huffman code for the given symbol frequencies.
+    pub fn from_frequencies(frequencies: &HashMap<S, u64>) -> Result<Self, BuildError> {
+        Ok(HuffmanTree::build_tree(frequencies)?.into())
+    }
+
+    pub fn encode_symbols<I>(&self, symbols: I) -> Result<Vec<bool>, EncodeError<S>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let mut bits = Vec::new();
+        for c in symbols {
+            let code = self
+                .encoding_table
+                .get(&c)
+                .ok_or(EncodeError::UnknownSymbol(c))?;
+            code.append_to(&mut bits);
+        }
+        Ok(bits)
+    }
+
+    /// Number of bits `encode_symbols` would produce for `symbols`, computed
+    /// from the code lengths without building the bit vector.
+    pub fn encoded_len_symbols<I>(&self, symbols: I) -> Result<usize, EncodeError<S>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let mut len = 0;
+        for c in symbols {
+            let code = self
+                .encoding_table
+                .get(&c)
+                .ok_or(EncodeError::UnknownSymbol(c))?;
+            len += code.len();
+        }
+        Ok(len)
+    }
+
+    /// Returns a fresh trie for decoding this code bit by bit.
+    pub fn decode_trie(&self) -> DecodeTrie<S> {
+        let mut trie = self.decode_trie.clone();
+        trie.reset();
+        trie
+    }
+
+    /// Decodes `data` by walking the decode trie one bit at a time.
+    ///
+    /// `data` must hold whole codewords only, with any byte padding already
+    /// stripped: a bit that leaves the trie, or a stream that ends partway
+    /// through a codeword, yields `DecodeError::InvalidBitstream`.
+    pub fn decode_symbols(&self, data: &[bool]) -> Result<Vec<S>, DecodeError> {
+        let mut decoded = Vec::new();
+        let mut trie = self.decode_trie();
+        for &bit in data {
+            if let Step::Symbol(c) = trie.step(bit)? {
+                decoded.push(c);
+            }
+        }
+        if !trie.at_root() {
+            return Err(DecodeError::InvalidBitstream);
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes exactly `n` symbols from the start of `data` and ignores the
+    /// bits after them, such as the zero padding of the last byte, even if
+    /// they would decode to more symbols. Fails with
+    /// `DecodeError::InvalidBitstream` if `data` holds fewer than `n`.
+    pub fn decode_symbols_n(&self, data: &[bool], n: usize) -> Result<Vec<S>, DecodeError> {
+        // Every symbol takes at least one bit, which bounds a corrupt `n`.
+        let mut decoded = Vec::with_capacity(n.min(data.len()));
+        let mut trie = self.decode_trie();
+        let mut bits = data.iter();
+        while decoded.len() < n {
+            let &bit = bits.next().ok_or(DecodeError::InvalidBitstream)?;
+            if let Step::Symbol(c) = trie.step(bit)? {
+                decoded.push(c);
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes messages that were encoded separately and concatenated, as
+    /// in a framed protocol: message `i` is the next `bit_lengths[i]` bits
+    /// of `data`. Every message must end on a codeword boundary, like
+    /// `decode_symbols` requires; one running past the end of `data` is an
+    /// invalid bitstream. Bits after the last message are ignored.
+    pub fn decode_symbols_framed<'a>(
+        &'a self,
+        data: &'a [bool],
+        bit_lengths: &'a [usize],
+    ) -> impl Iterator<Item = Result<Vec<S>, DecodeError>> + 'a {
+        let mut rest = data;
+        bit_lengths.iter().map(move |&len| {
+            if len > rest.len() {
+                rest = &[];
+                return Err(DecodeError::InvalidBitstream);
+            }
+            let (frame, tail) = rest.split_at(len);
+            rest = tail;
+            self.decode_symbols(frame)
+        })
+    }
+
+    /// Code length in bits of every symbol in the table.
+    pub fn code_lengths(&self) -> HashMap<S, u8> {
+        self.encoding_table
+            .iter()
+            .map(|(&c, code)| (c, code.len() as u8))
+            .collect()
+    }
+
+    /// Whether both codes contain the same symbols with the same code
+    /// lengths, even if the bit patterns differ. Such codes compress any
+    /// input to the same number of bits, so a code and its canonical form
+    /// are interchangeable for size.
+    pub fn assigns_same_lengths(&self, other: &HuffmanCode<S>) -> bool {
+        self.encoding_table.len() == other.encoding_table.len()
+            && self.encoding_table.iter().all(|(symbol, code)| {
+                other
+                    .encoding_table
+                    .get(symbol)
+                    .is_some_and(|other_code| other_code.len() == code.len())
+            })
+    }
+
+    /// Total output bits each symbol contributes when coding data with the
+    /// given `frequencies`: its code length times its count. Symbols the
+    /// code does not contain are left out.
+    pub fn symbol_bit_costs(&self, frequencies: &HashMap<S, u64>) -> HashMap<S, u64> {
+        frequencies
+            .iter()
+            .filter_map(|(c, &count)| {
+                let code = self.encoding_table.get(c)?;
+                Some((*c, code.len() as u64 * count))
+            })
+            .collect()
+    }
+
+    /// Whether the code lengths satisfy the Kraft inequality
+    /// `Σ 2^-len <= 1`. Every prefix code does; a table that does not has
+    /// more short codes than fit in a code tree ("over-subscribed").
+    /// Lengths beyond 64 bits are handled exactly.
+    pub fn satisfies_kraft(&self) -> bool {
+        lengths_satisfy_kraft(self.encoding_table.values().map(PackedCode::len))
+    }
+
+    /// Length in bits of the longest code, or 0 for an empty table.
+    pub fn max_code_len(&self) -> usize {
+        self.encoding_table
+            .values()
+            .map(PackedCode::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Table entries ordered by `(code length, symbol)`, shortest first.
+    pub fn codes_sorted(&self) -> Vec<(S, Vec<bool>)> {
+        let mut codes: Vec<_> = self
+            .encoding_table
+            .iter()
+            .map(|(&c, code)| (c, code.to_bits()))
+            .collect();
+        codes.sort_by(|(a, a_code), (b, b_code)| (a_code.len(), a).cmp(&(b_code.len(), b)));
+        codes
+    }
+
+    /// Serializes the code lengths as an entry count followed by one entry
+    /// per symbol, in symbol order: the symbol bytes and the code length in
+    /// bits (1 byte).
+    ///
+    /// The bit patterns themselves are not stored: `deserialize` rebuilds
+    /// them with `canonical`, so the round trip is exact only for canonical
+    /// codes. Use `serialize_codes` to keep arbitrary bit patterns.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_lengths(&mut out);
+        out
+    }
+
+    /// Rebuilds a code written by `serialize`. Lengths that cannot form a
+    /// prefix code (more codes of some length than fit) are rejected with
+    /// `FormatError::NotPrefixCode`.
+    pub fn deserialize(data: &[u8]) -> Result<Self, FormatError> {
+        Self::read_canonical(&mut ByteReader::new(data))
+    }
+
+    /// Serializes the full encoding table as an entry count followed by one
+    /// entry per symbol: the symbol bytes, the code length in bits (1 byte)
+    /// and the packed code bits.
+    pub fn serialize_codes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_table(&mut out);
+        out
+    }
+
+    /// Rebuilds a code written by `serialize_codes`, rejecting tables that
+    /// are not prefix codes with `FormatError::NotPrefixCode`.
+    pub fn deserialize_codes(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = ByteReader::new(data);
+        let code = Self::try_new(Self::read_table(&mut reader)?)?;
+        debug_assert!(code.satisfies_kraft());
+        Ok(code)
+    }
+
+    fn write_lengths(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.encoding_table.len() as u32).to_be_bytes());
+        for (&c, code) in sorted_by_symbol(&self.encoding_table) {
+            c.write_bytes(out);
+            out.push(code.len() as u8);
+        }
+    }
+
+    /// Reads lengths written by `write_lengths` and rebuilds their
+    /// canonical code.
+    pub(crate) fn read_canonical(reader: &mut ByteReader) -> Result<Self, FormatError> {
+        let lengths = Self::read_lengths(reader)?;
+        check_canonical_lengths(&lengths)?;
+        let code = Self::canonical(&lengths);
+        debug_assert!(code.satisfies_kraft());
+        Ok(code)
+    }
+
+    fn read_lengths(reader: &mut ByteReader) -> Result<HashMap<S, u8>, FormatError> {
+        let entry_count = reader.read_u32()?;
+        let mut lengths = HashMap::new();
+        for _ in 0..entry_count {
+            let c = reader.read_symbol()?;
+            lengths.insert(c, reader.read_u8()?);
+        }
+        Ok(lengths)
+    }
+
+    fn write_table(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.encoding_table.len() as u32).to_be_bytes());
+        for (&c, code) in sorted_by_symbol(&self.encoding_table) {
+            c.write_bytes(out);
+            out.push(code.len() as u8);
+            out.extend_from_slice(&pack_bits(&code.to_bits()));
+        }
+    }
+
+    fn read_table(reader: &mut ByteReader) -> Result<HashMap<S, Vec<bool>>, FormatError> {
+        let entry_count = reader.read_u32()?;
+        let mut encoding_table = HashMap::new();
+        for _ in 0..entry_count {
+            let c = reader.read_symbol()?;
+            let code_len = reader.read_u8()? as usize;
+            let code = unpack_bits(reader.take(code_len.div_ceil(8))?, code_len);
+            encoding_table.insert(c, code);
+        }
+        Ok(encoding_table)
+    }
+}
+
+impl<S: Symbol + Into<u32>> HuffmanCode<S> {
+    /// Code lengths indexed by symbol value, from 0 to `max_symbol`, with 0
+    /// for symbols the code does not contain. This is how DEFLATE and zlib
+    /// describe a canonical code, so together with `canonical` it converts
+    /// to and from their tables.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the code contains a symbol above `max_symbol`.
+    pub fn code_lengths_array(&self, max_symbol: usize) -> Vec<u8> {
+        let mut lengths = vec![0; max_symbol + 1];
+        for (symbol, code) in &self.encoding_table {
+            let index = (*symbol).into() as usize;
+            assert!(
+                index <= max_symbol,
+                "symbol {:?} is above max_symbol {}",
+                symbol,
+                max_symbol
+            );
+            lengths[index] = code.len() as u8;
+        }
+        lengths
+    }
+}
+
+/// The code the tree's root-to-leaf paths spell out, left being `0`.
+impl<S: Symbol> From<HuffmanTree<S>> for HuffmanCode<S> {
+    fn from(tree: HuffmanTree<S>) -> Self {
+        // A tree's paths always form a prefix code.
+        Self::new(tree.build_encoding_table())
+    }
+}
+
+impl HuffmanCode<char> {
+    /// Counts the chars of UTF-8 text read from `reader` and builds the
+    /// Huffman code for them. Fails like `create_counter` for unreadable or
+    /// non-UTF-8 input, and with `HuffmanError::EmptyInput` for empty input,
+    /// which has no code.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, HuffmanError> {
+        Ok(Self::from_frequencies(&create_counter(reader)?)?)
+    }
+
+    /// Encodes `data`, sizing the bit vector with `encoded_len` first so it
+    /// is allocated once instead of growing as codes are appended.
+    pub fn encode(&self, data: &str) -> Result<Vec<bool>, EncodeError> {
+        let mut bits = Vec::with_capacity(self.encoded_len(data)?);
+        for c in data.chars() {
+            // `encoded_len` has already checked every char is in the table.
+            self.encoding_table[&c].append_to(&mut bits);
+        }
+        Ok(bits)
+    }
+
+    /// Number of bits `encode` would produce for `data`. Fails like `encode`
+    /// for chars missing from the table.
+    pub fn encoded_len(&self, data: &str) -> Result<usize, EncodeError> {
+        self.encoded_len_symbols(data.chars())
+    }
+
+    /// Text version of `decode_symbols_n`: decodes exactly `n` chars and
+    /// ignores any bits after them.
+    pub fn decode_n(&self, data: &[bool], n: usize) -> Result<String, DecodeError> {
+        Ok(self.decode_symbols_n(data, n)?.into_iter().collect())
+    }
+
+    pub fn decode(&self, data: &[bool]) -> Result<String, DecodeError> {
+        Ok(self.decode_symbols(data)?.into_iter().collect())
+    }
+
+    /// Text version of `decode_symbols_framed`.
+    pub fn decode_framed<'a>(
+        &'a self,
+        data: &'a [bool],
+        bit_lengths: &'a [usize],
+    ) -> impl Iterator<Item = Result<String, DecodeError>> + 'a {
+        self.decode_symbols_framed(data, bit_lengths)
+            .map(|message| Ok(message?.into_iter().collect()))
+    }
+
+    /// Decodes the first `bit_len` bits of packed `bytes`, as written by
+    /// `pack_bits` or `encode_stream`, without unpacking them into a bool
+    /// vector first. A `bit_len` beyond the available bits is an invalid
+    /// bitstream, like a codeword cut short.
+    pub fn decode_bytes(&self, bytes: &[u8], bit_len: usize) -> Result<String, DecodeError> {
+        let mut reader = BitReader::new(bytes);
+        let mut trie = self.decode_trie();
+        let mut text = String::new();
+        for _ in 0..bit_len {
+            // Reading from a slice cannot fail; `None` means we ran past it.
+            let bit = reader
+                .read_bit()
+                .ok()
+                .flatten()
+                .ok_or(DecodeError::InvalidBitstream)?;
+            if let Step::Symbol(c) = trie.step(bit)? {
+                text.push(c);
+            }
+        }
+        if !trie.at_root() {
+            return Err(DecodeError::InvalidBitstream);
+        }
+        Ok(text)
+    }
+
+    /// Decodes `bits` like `decode`, but writes the UTF-8 bytes of each char
+    /// to `out` as soon as its codeword ends instead of collecting a
+    /// `String`. Nothing is buffered here, so wrap unbuffered writers in a
+    /// `BufWriter`. Invalid bits are reported as `InvalidData`; the chars
+    /// before them have been written by then.
+    pub fn decode_to<W: Write>(&self, bits: &[bool], mut out: W) -> io::Result<()> {
+        let mut trie = self.decode_trie();
+        let mut utf8 = [0u8; 4];
+        for &bit in bits {
+            if let Step::Symbol(c) = trie.step(bit)? {
+                out.write_all(c.encode_utf8(&mut utf8).as_bytes())?;
+            }
+        }
+        if !trie.at_root() {
+            return Err(DecodeError::InvalidBitstream.into());
+        }
+        Ok(())
+    }
+
+    /// Encodes `input` one line at a time, so only the current line is held
+    /// in memory, and writes packed bits like `encode_stream`. Lines are
+    /// read with their terminator, so `\r\n` and a missing final newline
+    /// come back unchanged. Returns the number of bits written.
+    pub fn encode_lines<R: BufRead, W: Write>(&self, mut input: R, output: W) -> io::Result<u64> {
+        let mut writer = BitWriter::new(output);
+        let mut line = String::new();
+        while input.read_line(&mut line)? > 0 {
+            for c in line.chars() {
+                let code = self.encoding_table.get(&c).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, EncodeError::UnknownSymbol(c))
+                })?;
+                code.write_to(&mut writer)?;
+            }
+            line.clear();
+        }
+        writer.flush()?;
+        Ok(writer.bits_written())
+    }
+
+    /// Encodes UTF-8 text read from `input` chunk by chunk, writing packed
+    /// bits (MSB-first, zero-padded at the end) to `output` as soon as whole
+    /// bytes are available. Returns the number of bits written, which is
+    /// needed to strip the padding when decoding.
+    pub fn encode_stream<R: Read, W: Write>(&self, input: R, output: W) -> io::Result<u64> {
+        self.encode_stream_with_options(input, output, &EncodeOptions::default())
+    }
+
+    /// Like `encode_stream`, with the buffering set by `options`.
+    pub fn encode_stream_with_options<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+        options: &EncodeOptions,
+    ) -> io::Result<u64> {
+        self.encode_stream_with_progress(input, output, options, 0, |_, _| {})
+    }
+
+    /// Like `encode_stream_with_options`, but calls `progress(processed,
+    /// total)` after every block is read with the number of input bytes
+    /// read so far. `total` is passed through unchanged; use the file size
+    /// when it is known and `0` otherwise (e.g. for stdin).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `options.block_size` is zero.
+    pub fn encode_stream_with_progress<R, W, F>(
+        &self,
+        input: R,
+        output: W,
+        options: &EncodeOptions,
+        total: u64,
+        mut progress: F,
+    ) -> io::Result<u64>
+    where
+        R: Read,
+        W: Write,
+        F: FnMut(u64, u64),
+    {
+        assert!(options.block_size > 0, "block size must be non-zero");
+        let mut symbols = Symbols::with_capacity(options.block_size, input);
+        let mut writer =
+            BitWriter::with_capacity(output, options.block_size).with_bit_order(options.bit_order);
+        let mut reported = 0u64;
+
+        while let Some(c) = symbols.next() {
+            let c = c?;
+            if symbols.bytes_read() > reported {
+                reported = symbols.bytes_read();
+                progress(reported, total);
+            }
+            let code = self.encoding_table.get(&c).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, EncodeError::UnknownSymbol(c))
+            })?;
+            code.write_to(&mut writer)?;
+        }
+
+        writer.flush()?;
+        Ok(writer.bits_written())
+    }
+
+    /// Decodes `bit_len` bits of packed input (as written by `encode_stream`)
+    /// and writes the UTF-8 text to `output` chunk by chunk. Invalid bits
+    /// are reported as `InvalidData`, like `decode` rejects them.
+    pub fn decode_stream<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+        bit_len: u64,
+    ) -> io::Result<()> {
+        self.decode_stream_with_order(input, output, bit_len, BitOrder::MsbFirst)
+    }
+
+    /// Decodes a stream written with `EncodeOptions::bit_order` set to
+    /// `order`, like `decode_stream` does for the default order.
+    pub fn decode_stream_with_order<R: Read, W: Write>(
+        &self,
+        input: R,
+        mut output: W,
+        bit_len: u64,
+        order: BitOrder,
+    ) -> io::Result<()> {
+        let mut reader = BitReader::new(input).with_bit_order(order);
+        let mut decoded = Vec::new();
+        let mut trie = self.decode_trie();
+
+        for _ in 0..bit_len {
+            let bit = reader.read_bit()?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input ends before the announced bit length",
+                )
+            })?;
+            if let Step::Symbol(c) = trie.step(bit)? {
+                let mut utf8 = [0u8; 4];
+                decoded.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                if decoded.len() >= STREAM_CHUNK_SIZE {
+                    output.write_all(&decoded)?;
+                    decoded.clear();
+                }
+            }
+        }
+        if !trie.at_root() {
+            return Err(DecodeError::InvalidBitstream.into());
+        }
+        output.write_all(&decoded)?;
+        Ok(())
+    }
+}
+
+impl HuffmanCode<u8> {
+    /// Decodes `data` to bytes and reads them as UTF-8 text the way
+    /// `String::from_utf8_lossy` does, replacing invalid sequences with
+    /// U+FFFD. Only the bitstream itself can fail to decode.
+    pub fn decode_lossy(&self, data: &[bool]) -> Result<String, DecodeError> {
+        Ok(String::from_utf8_lossy(&self.decode_symbols(data)?).into_owned())
+    }
+}
+
+/// The canonical codes for `lengths`, see `HuffmanCode::canonical`.
+///
+/// # Panics
+///
+/// Panics if a length exceeds 64 bits.
+pub(crate) fn canonical_table<S: Symbol>(lengths: &HashMap<S, u8>) -> HashMap<S, Vec<bool>> {
+    let mut symbols: Vec<(u8, S)> = lengths
+        .iter()
+        .filter(|(_, &len)| len > 0)
+        .map(|(&c, &len)| (len, c))
+        .collect();
+    symbols.sort();
+
+    let mut encoding_table = HashMap::new();
+    let mut code = 0u64;
+    let mut previous_len = symbols.first().map_or(0, |&(len, _)| len);
+    for (len, c) in symbols {
+        assert!(len <= 64, "code length {} exceeds 64 bits", len);
+        code <<= len - previous_len;
+        previous_len = len;
+        let bits = (0..len).rev().map(|i| (code >> i) & 1 == 1).collect();
+        encoding_table.insert(c, bits);
+        code += 1;
+    }
+    encoding_table
+}
+
+/// Rejects lengths `canonical_table` cannot turn into a prefix code: too
+/// many short codes, or codes longer than 64 bits.
+pub(crate) fn check_canonical_lengths<S>(lengths: &HashMap<S, u8>) -> Result<(), FormatError> {
+    let too_long = lengths.values().any(|&len| len > 64);
+    if too_long || !lengths_satisfy_kraft(lengths.values().map(|&len| len as usize)) {
+        return Err(FormatError::NotPrefixCode);
+    }
+    Ok(())
+}
+
+/// Encodes many messages with one char code without allocating for each.
+///
+/// `encode` returns a view into a buffer that is reused from one call to the
+/// next, so once it has grown to the longest message no further allocation
+/// happens; `encode_into` appends to a buffer owned by the caller instead.
+#[derive(Debug)]
+pub struct Encoder {
+    code: HuffmanCode<char>,
+    buffer: Vec<bool>,
+}
+
+impl Encoder {
+    pub fn new(code: HuffmanCode<char>) -> Self {
+        Self {
+            code,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn code(&self) -> &HuffmanCode<char> {
+        &self.code
+    }
+
+    /// Appends the bits of `data` to `out`. On an unknown char `out` is left
+    /// as it was.
+    pub fn encode_into(&mut self, data: &str, out: &mut Vec<bool>) -> Result<(), EncodeError> {
+        let start = out.len();
+        for c in data.chars() {
+            match self.code.encoding_table.get(&c) {
+                Some(code) => code.append_to(out),
+                None => {
+                    out.truncate(start);
+                    return Err(EncodeError::UnknownSymbol(c));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes `data` into the reusable buffer. The bits stay valid until the
+    /// next call.
+    pub fn encode(&mut self, data: &str) -> Result<&[bool], EncodeError> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        let result = self.encode_into(data, &mut buffer);
+        self.buffer = buffer;
+        result.map(|()| self.buffer.as_slice())
+    }
+}
+
+/// Whether codes of the given lengths fit in a binary code tree, i.e. the
+/// Kraft sum `Σ 2^-len` is at most 1. Zero lengths are skipped, matching
+/// `canonical`. Exact for any length.
+fn lengths_satisfy_kraft<I: IntoIterator<Item = usize>>(lengths: I) -> bool {
+    let mut counts: Vec<u64> = Vec::new();
+    for len in lengths.into_iter().filter(|&len| len > 0) {
+        if counts.len() <= len {
+            counts.resize(len + 1, 0);
+        }
+        counts[len] += 1;
+    }
+    // Going up from the longest codes, every two nodes of a level need one
+    // node of the level above. The root has room for two.
+    let nodes = counts
+        .iter()
+        .skip(1)
+        .rev()
+        .fold(0u64, |below, &count| count + below.div_ceil(2));
+    nodes <= 2
+}
+
+/// Entries of `map` in symbol order. Tables are written in this order so
+/// that equal tables serialize to identical bytes, whatever order the
+/// `HashMap` happens to iterate in.
+fn sorted_by_symbol<S: Symbol, V>(map: &HashMap<S, V>) -> Vec<(&S, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by_key(|&(&c, _)| c);
+    entries
+}
+
+fn write_frequencies<S: Symbol>(frequencies: &HashMap<S, u64>, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(frequencies.len() as u32).to_be_bytes());
+    for (&c, &count) in sorted_by_symbol(frequencies) {
+        c.write_bytes(out);
+        out.extend_from_slice(&count.to_be_bytes());
+    }
+}
+
+fn read_frequencies<S: Symbol>(reader: &mut ByteReader) -> Result<HashMap<S, u64>, FormatError> {
+    let entry_count = reader.read_u32()?;
+    let mut frequencies = HashMap::new();
+    // Tree weights are sums of counts, so the total has to fit as well.
+    let mut total = 0u64;
+    for _ in 0..entry_count {
+        let c = reader.read_symbol()?;
+        let count = reader.read_u64()?;
+        total = total
+            .checked_add(count)
+            .ok_or(FormatError::FrequencyOverflow)?;
+        frequencies.insert(c, count);
+    }
+    Ok(frequencies)
+}
+
+/// Serializes a frequency table as an entry count followed by the bytes and
+/// 8-byte count of each symbol. This is smaller than the code table and
+/// lets the decoder rebuild the same tree with `build_tree`.
+pub fn serialize_frequencies<S: Symbol>(frequencies: &HashMap<S, u64>) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_frequencies(frequencies, &mut out);
+    out
+}
+
+pub fn deserialize_frequencies<S: Symbol>(data: &[u8]) -> Result<HashMap<S, u64>, FormatError> {
+    read_frequencies(&mut ByteReader::new(data))
+}
+
+/// Writes a self-describing container for an encoded bitstream.
+///
+/// Layout, all integers big-endian:
+///
+/// ```text
+/// magic        4 bytes   b"HUFF"
+/// version      1 byte    FORMAT_VERSION
+/// symbol kind  1 byte    Symbol::KIND (0 = char, 1 = byte, 2 = u16)
+/// block type   1 byte    BlockType::Huffman (1)
+/// bit order    1 byte    BitOrder (0 = MSB first, 1 = LSB first)
+/// table format 1 byte    TableFormat (0 = frequencies)
+/// entry count  4 bytes
+/// entries      per symbol, in symbol order: symbol bytes (UTF-8 for
+///              chars), count (8 bytes)
+/// checksum     4 bytes   CRC-32 of the original data, see `symbols_checksum`
+/// symbol count 8 bytes   number of symbols the payload decodes to
+/// bit length   8 bytes   number of valid bits in the payload
+/// payload      packed bits, zero-padded to a whole byte
+/// ```
+///
+/// The payload is packed most significant bit first; use
+/// `write_compressed_container_with_options` for the other order or for
+/// the other table formats, which replace the entries.
+pub fn write_container<S: Symbol>(
+    frequencies: &HashMap<S, u64>,
+    bits: &[bool],
+    symbol_count: u64,
+    checksum: u32,
+) -> Vec<u8> {
+    write_huffman_container::<S>(
+        TableFormat::Frequencies,
+        &serialize_frequencies(frequencies),
+        bits,
+        symbol_count,
+        checksum,
+        BitOrder::MsbFirst,
+    )
+}
+
+fn write_huffman_container<S: Symbol>(
+    table_format: TableFormat,
+    table: &[u8],
+    bits: &[bool],
+    symbol_count: u64,
+    checksum: u32,
+    order: BitOrder,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(huffman_container_len(table.len(), bits.len()));
+    write_container_prefix::<S>(&mut out, BlockType::Huffman);
+    out.push(order as u8);
+    out.push(table_format as u8);
+    out.extend_from_slice(table);
+    out.extend_from_slice(&checksum.to_be_bytes());
+    out.extend_from_slice(&symbol_count.to_be_bytes());
+    out.extend_from_slice(&(bits.len() as u64).to_be_bytes());
+    out.extend_from_slice(&pack_bits_with_order(bits, order));
+    out
+}
+
+/// Writes a container holding `data`, the serialized symbols, as is. This
+/// is used when Huffman coding would make the data bigger.
+///
+/// After the magic, version, kind and block type (`BlockType::Stored`) it
+/// holds the 4-byte checksum, the data length as 8 bytes and the data.
+pub fn write_stored_container<S: Symbol>(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(stored_container_len(data.len()));
+    write_container_prefix::<S>(&mut out, BlockType::Stored);
+    out.extend_from_slice(&crc32(data).to_be_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Encodes `symbols` with `code` into a Huffman container, or stores them
+/// raw if the Huffman container would be larger. `code` must be the code
+/// built from `frequencies`, since that is what `read_container` rebuilds.
+///
+/// The choice is made from the code lengths, before encoding anything.
+pub fn write_compressed_container<S: Symbol>(
+    frequencies: &HashMap<S, u64>,
+    code: &HuffmanCode<S>,
+    symbols: &[S],
+) -> Result<Vec<u8>, EncodeError<S>> {
+    write_compressed_container_with_options(frequencies, code, symbols, &EncodeOptions::default())
+}
+
+/// Like `write_compressed_container`, packing a Huffman payload in
+/// `options.bit_order` and describing the code in `options.table_format`.
+/// Both are recorded in the container, so `read_container` needs no
+/// options.
+///
+/// With `TableFormat::CanonicalLengths` the payload is coded with the
+/// canonical code of `code`'s lengths, which compresses exactly as well.
+/// Codes longer than 64 bits have no canonical form here, so such a code
+/// is stored with `TableFormat::Codes` instead.
+pub fn write_compressed_container_with_options<S: Symbol>(
+    frequencies: &HashMap<S, u64>,
+    code: &HuffmanCode<S>,
+    symbols: &[S],
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, EncodeError<S>> {
+    let canonical;
+    let (table_format, table, code) = match options.table_format {
+        TableFormat::Frequencies => (
+            TableFormat::Frequencies,
+            serialize_frequencies(frequencies),
+            code,
+        ),
+        TableFormat::CanonicalLengths if code.max_code_len() <= 64 => {
+            canonical = HuffmanCode::canonical(&code.code_lengths());
+            (
+                TableFormat::CanonicalLengths,
+                canonical.serialize(),
+                &canonical,
+            )
+        }
+        TableFormat::Codes | TableFormat::CanonicalLengths => {
+            (TableFormat::Codes, code.serialize_codes(), code)
+        }
+    };
+
+    let bit_len = code.encoded_len_symbols(symbols.iter().copied())?;
+    let data = symbols_bytes(symbols);
+
+    if huffman_container_len(table.len(), bit_len) > stored_container_len(data.len()) {
+        return Ok(write_stored_container::<S>(&data));
+    }
+    let bits = code.encode_symbols(symbols.iter().copied())?;
+    Ok(write_huffman_container::<S>(
+        table_format,
+        &table,
+        &bits,
+        symbols.len() as u64,
+        crc32(&data),
+        options.bit_order,
+    ))
+}
+
+/// Counts `symbols`, builds their code and writes the smaller of a Huffman
+/// and a stored container, laid out as `options` asks.
+///
+/// With `options.sample_bytes` only a prefix of that many bytes is counted.
+/// If a symbol first appears after the sample, the code cannot encode it
+/// and the data is stored instead, so the result always decodes. Empty
+/// input, or an empty sample, has no code either: empty input gets an
+/// empty table like `write_container` writes, anything else is stored.
+///
+/// With `options.transform` the serialized symbols are transformed and
+/// compressed as bytes, see `write_transformed_container`.
+pub fn compress_symbols<S: Symbol>(symbols: &[S], options: &EncodeOptions) -> Vec<u8> {
+    if let Some(transform) = options.transform {
+        let data = symbols_bytes(symbols);
+        let inner = compress_symbols(
+            &transform.apply(&data),
+            &EncodeOptions {
+                transform: None,
+                ..*options
+            },
+        );
+        return write_transformed_container::<S>(transform, crc32(&data), &inner);
+    }
+    let frequencies = sample_frequencies(symbols, options.sample_bytes);
+    let code = match HuffmanCode::from_frequencies(&frequencies) {
+        Ok(code) => code,
+        Err(_) if symbols.is_empty() => {
+            return write_container::<S>(&frequencies, &[], 0, crc32(b""))
+        }
+        Err(_) => return write_stored_container::<S>(&symbols_bytes(symbols)),
+    };
+    write_compressed_container_with_options(&frequencies, &code, symbols, options)
+        .unwrap_or_else(|_| write_stored_container::<S>(&symbols_bytes(symbols)))
+}
+
+/// Counts the symbols that start within the first `sample_bytes` bytes of
+/// their serialized form, or all of them.
+fn sample_frequencies<S: Symbol>(symbols: &[S], sample_bytes: Option<u64>) -> HashMap<S, u64> {
+    let Some(limit) = sample_bytes else {
+        return count_symbols(symbols.iter().copied());
+    };
+    let mut frequencies = HashMap::new();
+    let mut bytes = Vec::new();
+    for &symbol in symbols {
+        if bytes.len() as u64 >= limit {
+            break;
+        }
+        symbol.write_bytes(&mut bytes);
+        *frequencies.entry(symbol).or_insert(0) += 1;
+    }
+    frequencies
+}
+
+fn symbols_bytes<S: Symbol>(symbols: &[S]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for symbol in symbols {
+        symbol.write_bytes(&mut data);
+    }
+    data
+}
+
+/// Writes a container whose payload is `data`, the serialized symbols,
+/// coded with `AdaptiveHuffman`. No frequency table is stored since the
+/// decoder rebuilds the model as it goes.
+///
+/// After the magic, version, kind and block type (`BlockType::Adaptive`)
+/// it holds the 4-byte checksum, the payload length as 8 bytes and the
+/// adaptive bitstream, which carries its own end marker.
+pub fn write_adaptive_container<S: Symbol>(data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    AdaptiveHuffman::encode(data, &mut payload).expect("writing to a Vec cannot fail");
+    let mut out = Vec::with_capacity(CONTAINER_PREFIX_LEN + 4 + 8 + payload.len());
+    write_container_prefix::<S>(&mut out, BlockType::Adaptive);
+    out.extend_from_slice(&crc32(data).to_be_bytes());
+    out.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+/// Writes a container for data that was transformed with `transform` and
+/// then compressed into `inner`, a byte container. `checksum` is the CRC-32
+/// of the serialized symbols before the transform.
+///
+/// After the magic, version, kind and block type (`BlockType::Transformed`)
+/// it holds the transform (1 byte), the 4-byte checksum, the length of
+/// `inner` as 8 bytes and `inner` itself.
+pub fn write_transformed_container<S: Symbol>(
+    transform: Transform,
+    checksum: u32,
+    inner: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(CONTAINER_PREFIX_LEN + 1 + 4 + 8 + inner.len());
+    write_container_prefix::<S>(&mut out, BlockType::Transformed);
+    out.push(transform as u8);
+    out.extend_from_slice(&checksum.to_be_bytes());
+    out.extend_from_slice(&(inner.len() as u64).to_be_bytes());
+    out.extend_from_slice(inner);
+    out
+}
+
+fn write_container_prefix<S: Symbol>(out: &mut Vec<u8>, block_type: BlockType) {
+    out.extend_from_slice(FORMAT_MAGIC);
+    out.push(FORMAT_VERSION);
+    out.push(S::KIND);
+    out.push(block_type as u8);
+}
+
+fn huffman_container_len(table_len: usize, bit_len: usize) -> usize {
+    CONTAINER_PREFIX_LEN + 2 + table_len + 4 + 8 + 8 + bit_len.div_ceil(8)
+}
+
+fn stored_container_len(data_len: usize) -> usize {
+    CONTAINER_PREFIX_LEN + 4 + 8 + data_len
+}
+
+/// How the payload of a container is encoded, stored as one byte in the
+/// header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockType {
+    /// The serialized symbols, uncompressed.
+    Stored = 0,
+    /// A code table followed by the Huffman-coded bits.
+    Huffman = 1,
+    /// Symbol bytes coded in one pass with `AdaptiveHuffman`.
+    Adaptive = 2,
+    /// A byte container of the symbol bytes after a `Transform`.
+    Transformed = 3,
+}
+
+impl TryFrom<u8> for BlockType {
+    type Error = FormatError;
+
+    fn try_from(byte: u8) -> Result<Self, FormatError> {
+        match byte {
+            0 => Ok(BlockType::Stored),
+            1 => Ok(BlockType::Huffman),
+            2 => Ok(BlockType::Adaptive),
+            3 => Ok(BlockType::Transformed),
+            _ => Err(FormatError::UnknownBlockType(byte)),
+        }
+    }
+}
+
+/// Contents of a container read by `read_container`.
+#[derive(Debug)]
+pub enum Container<S = char> {
+    /// A Huffman-coded payload with the code rebuilt from its table.
+    Huffman {
+        code: HuffmanCode<S>,
+        bits: Vec<bool>,
+        /// Number of symbols to decode; later bits are ignored.
+        symbol_count: u64,
+        checksum: u32,
+    },
+    /// Symbols stored without compression.
+    Stored { symbols: Vec<S>, checksum: u32 },
+    /// An `AdaptiveHuffman` bitstream of the serialized symbols.
+    Adaptive { payload: Vec<u8>, checksum: u32 },
+    /// The symbol bytes after `transform`, compressed in a byte container.
+    Transformed {
+        transform: Transform,
+        inner: Box<Container<u8>>,
+        checksum: u32,
+    },
+}
+
+impl<S: Symbol> Container<S> {
+    pub fn block_type(&self) -> BlockType {
+        match self {
+            Container::Huffman { .. } => BlockType::Huffman,
+            Container::Stored { .. } => BlockType::Stored,
+            Container::Adaptive { .. } => BlockType::Adaptive,
+            Container::Transformed { .. } => BlockType::Transformed,
+        }
+    }
+
+    /// CRC-32 of the original data, checked by the `decode` methods.
+    pub fn checksum(&self) -> u32 {
+        match self {
+            Container::Huffman { checksum, .. }
+            | Container::Stored { checksum, .. }
+            | Container::Adaptive { checksum, .. }
+            | Container::Transformed { checksum, .. } => *checksum,
+        }
+    }
+
+    /// Decodes the payload and checks it against the stored checksum,
+    /// failing with `DecodeError::ChecksumMismatch` if the data is corrupt.
+    pub fn decode_symbols(&self) -> Result<Vec<S>, DecodeError> {
+        self.decode_symbols_with_options(&DecodeOptions::default())
+    }
+
+    /// Like `decode_symbols`, within the limits of `options`.
+    pub fn decode_symbols_with_options(
+        &self,
+        options: &DecodeOptions,
+    ) -> Result<Vec<S>, DecodeError> {
+        let limit = options.max_output_bytes.unwrap_or(u64::MAX);
+        let symbols = match self {
+            Container::Huffman {
+                code,
+                bits,
+                symbol_count,
+                ..
+            } => decode_huffman(code, bits, limited_count(*symbol_count, limit)?, limit)?,
+            Container::Stored { symbols, .. } => symbols.clone(),
+            Container::Adaptive { payload, .. } => decode_adaptive(payload, limit)?,
+            Container::Transformed {
+                transform, inner, ..
+            } => {
+                // Run-length coding turns a pair into three bytes, so the
+                // transformed bytes are at most 1.5 times the output.
+                let inner_options = DecodeOptions {
+                    max_output_bytes: Some(limit.saturating_add(limit / 2 + 1)),
+                };
+                let data =
+                    inner
+                        .decode_symbols_with_options(&inner_options)
+                        .map_err(|e| match e {
+                            DecodeError::OutputLimitExceeded { .. } => {
+                                DecodeError::OutputLimitExceeded { limit }
+                            }
+                            e => e,
+                        })?;
+                split_symbols(&transform.invert_limited(&data, limit)?)?
+            }
+        };
+        check_output_len(symbols_bytes(&symbols).len(), limit)?;
+        verify_checksum(self.checksum(), symbols_checksum(symbols.iter().copied()))?;
+        Ok(symbols)
+    }
+}
+
+impl Container<char> {
+    pub fn decode(&self) -> Result<String, DecodeError> {
+        self.decode_with_options(&DecodeOptions::default())
+    }
+
+    /// Like `decode`, within the limits of `options`.
+    pub fn decode_with_options(&self, options: &DecodeOptions) -> Result<String, DecodeError> {
+        let limit = options.max_output_bytes.unwrap_or(u64::MAX);
+        let text: String = match self {
+            Container::Huffman {
+                code,
+                bits,
+                symbol_count,
+                ..
+            } => decode_huffman(code, bits, limited_count(*symbol_count, limit)?, limit)?
+                .into_iter()
+                .collect(),
+            Container::Stored { symbols, .. } => symbols.iter().collect(),
+            Container::Adaptive { payload, .. } => decode_adaptive::<char>(payload, limit)?
+                .into_iter()
+                .collect(),
+            // `decode_symbols_with_options` checks the limit and the
+            // checksum already.
+            Container::Transformed { .. } => {
+                return Ok(self
+                    .decode_symbols_with_options(options)?
+                    .into_iter()
+                    .collect())
+            }
+        };
+        check_output_len(text.len(), limit)?;
+        verify_checksum(self.checksum(), crc32(text.as_bytes()))?;
+        Ok(text)
+    }
+}
+
+impl Container<u8> {
+    /// Decodes the bytes like `decode_symbols`, checksum included, and
+    /// reads them as text with U+FFFD for invalid UTF-8. This is for byte
+    /// containers that are expected to hold mostly text.
+    pub fn decode_lossy(&self) -> Result<String, DecodeError> {
+        Ok(String::from_utf8_lossy(&self.decode_symbols()?).into_owned())
+    }
+}
+
+/// Decodes the first `n` symbols of a Huffman payload like
+/// `HuffmanCode::decode_symbols_n`, failing as soon as their serialized
+/// bytes pass `limit`. The symbol count alone does not bound the output,
+/// since a char takes up to four bytes.
+fn decode_huffman<S: Symbol>(
+    code: &HuffmanCode<S>,
+    bits: &[bool],
+    n: usize,
+    limit: u64,
+) -> Result<Vec<S>, DecodeError> {
+    if limit == u64::MAX {
+        return code.decode_symbols_n(bits, n);
+    }
+    let mut decoded = Vec::with_capacity(n.min(bits.len()));
+    let mut trie = code.decode_trie();
+    let mut bits = bits.iter();
+    let mut symbol_bytes = Vec::new();
+    let mut output_len = 0u64;
+    while decoded.len() < n {
+        let &bit = bits.next().ok_or(DecodeError::InvalidBitstream)?;
+        if let Step::Symbol(symbol) = trie.step(bit)? {
+            symbol_bytes.clear();
+            symbol.write_bytes(&mut symbol_bytes);
+            output_len += symbol_bytes.len() as u64;
+            if output_len > limit {
+                return Err(DecodeError::OutputLimitExceeded { limit });
+            }
+            decoded.push(symbol);
+        }
+    }
+    Ok(decoded)
+}
+
+/// Decodes an adaptive payload of at most `limit` bytes and splits the
+/// bytes back into symbols. A stream without its end marker is an invalid
+/// bitstream.
+fn decode_adaptive<S: Symbol>(payload: &[u8], limit: u64) -> Result<Vec<S>, DecodeError> {
+    let mut output = LimitedVec {
+        data: Vec::new(),
+        limit,
+        exceeded: false,
+    };
+    if AdaptiveHuffman::decode(payload, &mut output).is_err() {
+        return Err(if output.exceeded {
+            DecodeError::OutputLimitExceeded { limit }
+        } else {
+            DecodeError::InvalidBitstream
+        });
+    }
+    split_symbols(&output.data)
+}
+
+/// A `Vec` writer that fails instead of growing past `limit` bytes.
+struct LimitedVec {
+    data: Vec<u8>,
+    limit: u64,
+    exceeded: bool,
+}
+
+impl Write for LimitedVec {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if (self.data.len() + buf.len()) as u64 > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("decoded output exceeds the limit"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A stored symbol count as a `usize`, checked against an output `limit`
+/// before anything is decoded: every symbol takes at least one byte.
+fn limited_count(symbol_count: u64, limit: u64) -> Result<usize, DecodeError> {
+    if symbol_count > limit {
+        return Err(DecodeError::OutputLimitExceeded { limit });
+    }
+    checked_count(symbol_count)
+}
+
+fn check_output_len(len: usize, limit: u64) -> Result<(), DecodeError> {
+    if len as u64 > limit {
+        return Err(DecodeError::OutputLimitExceeded { limit });
+    }
+    Ok(())
+}
+
+/// Splits decoded bytes back into symbols; bytes that are not whole symbols
+/// are an invalid bitstream.
+fn split_symbols<S: Symbol>(data: &[u8]) -> Result<Vec<S>, DecodeError> {
+    let mut reader = ByteReader::new(data);
+    let mut symbols = Vec::new();
+    while reader.pos < reader.data.len() {
+        symbols.push(
+            reader
+                .read_symbol()
+                .map_err(|_| DecodeError::InvalidBitstream)?,
+        );
+    }
+    Ok(symbols)
+}
+
+/// A stored symbol count as a `usize`. Each symbol takes at least one bit,
+/// so a count beyond the address space cannot be satisfied anyway.
+fn checked_count(symbol_count: u64) -> Result<usize, DecodeError> {
+    usize::try_from(symbol_count).map_err(|_| DecodeError::InvalidBitstream)
+}
+
+fn verify_checksum(expected: u32, actual: u32) -> Result<(), DecodeError> {
+    if expected != actual {
+        return Err(DecodeError::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Reads a container written by `write_container`, `write_stored_container`,
+/// `write_adaptive_container` or `write_transformed_container`. For Huffman
+/// containers the code is rebuilt from the stored table but the payload is
+/// not decoded yet; use the `Container` decode methods, which also verify
+/// the checksum.
+pub fn read_container<S: Symbol>(data: &[u8]) -> Result<Container<S>, FormatError> {
+    let mut reader = ByteReader::new(data);
+    if reader.take(FORMAT_MAGIC.len())? != FORMAT_MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+    let version = reader.read_u8()?;
+    if version != FORMAT_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    let kind = reader.read_u8()?;
+    if kind != S::KIND {
+        return Err(FormatError::SymbolKindMismatch(kind));
+    }
+    let block_type = BlockType::try_from(reader.read_u8()?)?;
+
+    match block_type {
+        BlockType::Stored => {
+            let checksum = reader.read_u32()?;
+            let data_len =
+                usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?;
+            let mut stored = ByteReader::new(reader.take(data_len)?);
+            let mut symbols = Vec::new();
+            while stored.pos < stored.data.len() {
+                symbols.push(stored.read_symbol()?);
+            }
+            return Ok(Container::Stored { symbols, checksum });
+        }
+        BlockType::Adaptive => {
+            let checksum = reader.read_u32()?;
+            let payload_len =
+                usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?;
+            let payload = reader.take(payload_len)?.to_vec();
+            return Ok(Container::Adaptive { payload, checksum });
+        }
+        BlockType::Transformed => {
+            let transform = Transform::try_from(reader.read_u8()?)?;
+            let checksum = reader.read_u32()?;
+            let inner_len =
+                usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?;
+            let inner = read_container::<u8>(reader.take(inner_len)?)?;
+            if inner.block_type() == BlockType::Transformed {
+                return Err(FormatError::NestedTransform);
+            }
+            return Ok(Container::Transformed {
+                transform,
+                inner: Box::new(inner),
+                checksum,
+            });
+        }
+        BlockType::Huffman => {}
+    }
+
+    let order = match reader.read_u8()? {
+        0 => BitOrder::MsbFirst,
+        1 => BitOrder::LsbFirst,
+        byte => return Err(FormatError::UnknownBitOrder(byte)),
+    };
+    let code = match TableFormat::try_from(reader.read_u8()?)? {
+        TableFormat::Frequencies => {
+            let frequencies = read_frequencies(&mut reader)?;
+            if frequencies.is_empty() {
+                HuffmanCode::new(HashMap::new())
+            } else {
+                HuffmanCode::from_frequencies(&frequencies).map_err(|_| FormatError::EmptyTable)?
+            }
+        }
+        TableFormat::Codes => HuffmanCode::try_new(HuffmanCode::read_table(&mut reader)?)?,
+        TableFormat::CanonicalLengths => HuffmanCode::read_canonical(&mut reader)?,
+    };
+    let checksum = reader.read_u32()?;
+    let symbol_count = reader.read_u64()?;
+    let bit_len = reader.read_u64()? as usize;
+    let payload = reader.take(bit_len.div_ceil(8))?;
+    if code.encoding_table.is_empty() && symbol_count > 0 {
+        // Only empty input is written without a code.
+        return Err(FormatError::EmptyTable);
+    }
+
+    Ok(Container::Huffman {
+        code,
+        bits: unpack_bits_with_order(payload, bit_len, order),
+        symbol_count,
+        checksum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::{count_chars, count_symbols};
+    use std::io::Cursor;
+
+    /// Small xorshift generator so the property tests stay reproducible
+    /// without pulling in a dependency.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn sample_code() -> HuffmanCode {
+        let mut table = HashMap::new();
+        table.insert('a', vec![false]);
+        table.insert('b', vec![true, false]);
+        table.insert('c', vec![true, true]);
+        HuffmanCode::new(table)
+    }
+
+    #[test]
+    fn test_encode() {
+        let code = sample_code();
+        let bits = code.encode("abca").unwrap();
+        assert_eq!(bits, vec![false, true, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_encode_empty() {
+        let code = sample_code();
+        assert_eq!(code.encode("").unwrap(), Vec::<bool>::new());
+    }
+
+    #[test]
+    fn test_encoded_len_matches_encode() {
+        let code = sample_code();
+        for input in ["", "a", "abc", "cabbac", "aaaaaaaabbbcc"] {
+            assert_eq!(
+                code.encoded_len(input).unwrap(),
+                code.encode(input).unwrap().len()
+            );
+        }
+        assert_eq!(
+            code.encoded_len("abz").unwrap_err(),
+            EncodeError::UnknownSymbol('z')
+        );
+    }
+
+    #[test]
+    fn test_encode_preallocates_exactly() {
+        let code = sample_code();
+        for input in ["", "a", "cabbac", &"aaaaaaaabbbcc".repeat(100)] {
+            let bits = code.encode(input).unwrap();
+            assert_eq!(bits, code.encode_symbols(input.chars()).unwrap());
+            // One allocation of the final size, never grown.
+            assert_eq!(bits.capacity(), bits.len());
+        }
+    }
+
+    #[test]
+    fn test_decode_framed_splits_concatenated_messages() {
+        let code = sample_code();
+        let messages = ["abc", "", "cabbac"];
+        let mut data = Vec::new();
+        let mut bit_lengths = Vec::new();
+        for message in messages {
+            let bits = code.encode(message).unwrap();
+            bit_lengths.push(bits.len());
+            data.extend(bits);
+        }
+
+        let decoded: Vec<String> = code
+            .decode_framed(&data, &bit_lengths)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(decoded, messages);
+
+        // Lengths that cut a codeword or run past the data fail only the
+        // messages they affect.
+        let short = [bit_lengths[0], 1, 1000];
+        let results: Vec<_> = code.decode_framed(&data, &short).collect();
+        assert_eq!(results[0], Ok("abc".to_string()));
+        assert_eq!(results[1], Err(DecodeError::InvalidBitstream));
+        assert_eq!(results[2], Err(DecodeError::InvalidBitstream));
+    }
+
+    #[test]
+    fn test_code_from_tree() {
+        let text = "abracadabra";
+        let tree = HuffmanTree::build_tree(&count_chars(text)).unwrap();
+        let table = tree.build_encoding_table();
+        let code = HuffmanCode::from(tree);
+        assert_eq!(code.codes_sorted(), HuffmanCode::new(table).codes_sorted());
+
+        let bits = code.encode(text).unwrap();
+        assert_eq!(bits.len(), 23);
+        assert_eq!(code.decode(&bits).unwrap(), text);
+    }
+
+    #[test]
+    fn test_encode_unknown_symbol() {
+        let code = sample_code();
+        assert_eq!(code.encode("abz"), Err(EncodeError::UnknownSymbol('z')));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let code = sample_code();
+        let bits = code.encode("cabbac").unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), "cabbac");
+    }
+
+    #[test]
+    fn test_decode_hello_world() {
+        let input = "hello world";
+        let mut frequencies = HashMap::new();
+        for c in input.chars() {
+            *frequencies.entry(c).or_insert(0) += 1;
+        }
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+        let code = HuffmanCode::new(tree.build_encoding_table());
+
+        let bits = code.encode(input).unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), input);
+    }
+
+    #[test]
+    fn test_decode_bytes_matches_unpack_then_decode() {
+        let text = "packed bytes decode the same ünï 🎉 ".repeat(30);
+        let code = code_for(&text);
+        let bits = code.encode(&text).unwrap();
+        let bytes = pack_bits(&bits);
+
+        assert_eq!(
+            code.decode_bytes(&bytes, bits.len()).unwrap(),
+            code.decode(&unpack_bits(&bytes, bits.len())).unwrap()
+        );
+        assert_eq!(code.decode_bytes(&bytes, bits.len()).unwrap(), text);
+        assert_eq!(code.decode_bytes(&bytes, 0).unwrap(), "");
+        assert_eq!(
+            code.decode_bytes(&bytes, bytes.len() * 8 + 1),
+            Err(DecodeError::InvalidBitstream)
+        );
+    }
+
+    #[test]
+    fn test_decode_to_matches_decode() {
+        let input = "Grüße, 🎉 and more text\n".repeat(20);
+        let code = code_for(&input);
+        let bits = code.encode(&input).unwrap();
+
+        let mut out = Vec::new();
+        code.decode_to(&bits, &mut out).unwrap();
+        assert_eq!(out, code.decode(&bits).unwrap().into_bytes());
+
+        let err = code.decode_to(&bits[..bits.len() - 1], Vec::new());
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_rejects_incomplete_trailing_codeword() {
+        let code = sample_code();
+        let mut bits = code.encode("ab").unwrap();
+        bits.push(true);
+        assert_eq!(code.decode(&bits), Err(DecodeError::InvalidBitstream));
+    }
+
+    #[test]
+    fn test_decode_rejects_bits_outside_the_code() {
+        let mut table = HashMap::new();
+        table.insert('a', vec![false]);
+        table.insert('b', vec![true, false]);
+        let code = HuffmanCode::new(table);
+
+        assert_eq!(code.decode(&[false, true, false]).unwrap(), "ab");
+        // "11" is not a prefix of any codeword.
+        assert_eq!(
+            code.decode(&[false, true, true, false]),
+            Err(DecodeError::InvalidBitstream)
+        );
+    }
+
+    #[test]
+    fn test_decode_stream_rejects_corrupted_bits() {
+        let code = HuffmanCode::from_frequencies(&[('a', 1)].into_iter().collect()).unwrap();
+        let err = code
+            .decode_stream(Cursor::new(vec![0b0100_0000]), Vec::new(), 2)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn sample_frequencies() -> HashMap<char, u64> {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 4);
+        frequencies.insert('b', 2);
+        frequencies.insert('c', 1);
+        frequencies.insert('d', 5);
+        frequencies
+    }
+
+    #[test]
+    fn test_symbol_bit_costs() {
+        let frequencies = sample_frequencies();
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let costs = code.symbol_bit_costs(&frequencies);
+        assert_eq!(
+            costs,
+            HashMap::from([('d', 5), ('a', 8), ('b', 6), ('c', 3)])
+        );
+        let text = "dddddaaaabbc";
+        assert_eq!(
+            costs.values().sum::<u64>(),
+            code.encoded_len(text).unwrap() as u64
+        );
+
+        let with_unknown = HashMap::from([('a', 10), ('z', 7)]);
+        assert_eq!(
+            code.symbol_bit_costs(&with_unknown),
+            HashMap::from([('a', 20)])
+        );
+    }
+
+    #[test]
+    fn test_codes_sorted_by_length_then_symbol() {
+        let frequencies = sample_frequencies();
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let sorted = code.codes_sorted();
+        let order: Vec<_> = sorted.iter().map(|(c, bits)| (*c, bits.len())).collect();
+        assert_eq!(order, vec![('d', 1), ('a', 2), ('b', 3), ('c', 3)]);
+        for pair in sorted.windows(2) {
+            assert!(frequencies[&pair[0].0] >= frequencies[&pair[1].0]);
+        }
+        for (c, bits) in &sorted {
+            assert_eq!(&code.encoding_table[c].to_bits(), bits);
+        }
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let frequencies = sample_frequencies();
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode("abcdabd").unwrap();
+        let bytes = write_container(&frequencies, &bits, 7, crc32(b"abcdabd"));
+        assert_eq!(&bytes[..4], b"HUFF");
+        assert_eq!(bytes[4], FORMAT_VERSION);
+
+        let container = read_container::<char>(&bytes).unwrap();
+        match &container {
+            Container::Huffman {
+                code: restored,
+                bits: restored_bits,
+                ..
+            } => {
+                assert_eq!(restored.encoding_table, code.encoding_table);
+                assert_eq!(restored_bits, &bits);
+            }
+            _ => panic!("expected a Huffman container"),
+        }
+        assert_eq!(container.decode().unwrap(), "abcdabd");
+    }
+
+    #[test]
+    fn test_decode_n_ignores_padding_that_decodes() {
+        let code = sample_code();
+        let bits = code.encode("b").unwrap();
+        // 'a' is the one-bit code 0, so the six zero padding bits of the
+        // packed byte would decode to six extra 'a's.
+        let padded = padded_to_byte(&bits);
+        assert_eq!(code.decode(&padded).unwrap(), "baaaaaa");
+        assert_eq!(code.decode_n(&padded, 1).unwrap(), "b");
+        assert_eq!(code.decode_n(&padded, 3).unwrap(), "baa");
+        assert_eq!(code.decode_n(&padded, 0).unwrap(), "");
+        assert_eq!(code.decode_n(&bits, 2), Err(DecodeError::InvalidBitstream));
+
+        let frequencies = sample_frequencies();
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode("a").unwrap();
+        let container = read_container::<char>(&write_container(
+            &frequencies,
+            &padded_to_byte(&bits),
+            1,
+            crc32(b"a"),
+        ))
+        .unwrap();
+        assert_eq!(container.decode().unwrap(), "a");
+    }
+
+    fn padded_to_byte(bits: &[bool]) -> Vec<bool> {
+        unpack_bits(&pack_bits(bits), bits.len().div_ceil(8) * 8)
+    }
+
+    #[test]
+    fn test_container_detects_corrupted_payload() {
+        let frequencies = sample_frequencies();
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let text = "dddddaaaabbc";
+        let bits = code.encode(text).unwrap();
+        let mut bytes = write_container(
+            &frequencies,
+            &bits,
+            text.len() as u64,
+            crc32(text.as_bytes()),
+        );
+
+        // Flipping the last bit turns the final 'c' (100) into 'b' (101): the
+        // stream still decodes, to other text.
+        let payload_start = bytes.len() - bits.len().div_ceil(8);
+        let last = bits.len() - 1;
+        bytes[payload_start + last / 8] ^= 0x80 >> (last % 8);
+        let container = read_container::<char>(&bytes).unwrap();
+        assert!(matches!(
+            container.decode(),
+            Err(DecodeError::ChecksumMismatch { expected, .. }) if expected == crc32(text.as_bytes())
+        ));
+    }
+
+    #[test]
+    fn test_incompressible_input_is_stored() {
+        let mut rng = XorShift(0x0123_4567_89AB_CDEF);
+        let data: Vec<u8> = (0..4096).map(|_| rng.next() as u8).collect();
+        let frequencies = count_symbols(data.iter().copied());
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let bytes = write_compressed_container(&frequencies, &code, &data).unwrap();
+        assert_eq!(bytes[6], BlockType::Stored as u8);
+        assert_eq!(bytes.len(), stored_container_len(data.len()));
+
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(container.block_type(), BlockType::Stored);
+        assert_eq!(container.decode_symbols().unwrap(), data);
+    }
+
+    #[test]
+    fn test_compressible_input_uses_huffman() {
+        let text: Vec<char> = "aaaaaaaabbbbccd".repeat(50).chars().collect();
+        let frequencies = count_symbols(text.iter().copied());
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let bytes = write_compressed_container(&frequencies, &code, &text).unwrap();
+        assert_eq!(bytes[6], BlockType::Huffman as u8);
+        let bit_len = code.encoded_len_symbols(text.iter().copied()).unwrap();
+        assert_eq!(
+            bytes.len(),
+            huffman_container_len(serialize_frequencies(&frequencies).len(), bit_len)
+        );
+
+        let container = read_container::<char>(&bytes).unwrap();
+        assert_eq!(container.block_type(), BlockType::Huffman);
+        assert_eq!(container.decode_symbols().unwrap(), text);
+    }
+
+    #[test]
+    fn test_stored_container_detects_corruption() {
+        let mut bytes = write_stored_container::<char>("stored text".as_bytes());
+        let last = bytes.len() - 1;
+        bytes[last] = b'X';
+        assert!(matches!(
+            read_container::<char>(&bytes).unwrap().decode(),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_adaptive_container_round_trip() {
+        let text = "adaptive containers need no table: ünï 🎉\n".repeat(20);
+        let bytes = write_adaptive_container::<char>(text.as_bytes());
+        assert_eq!(bytes[6], BlockType::Adaptive as u8);
+        assert!(bytes.len() < text.len());
+
+        let container = read_container::<char>(&bytes).unwrap();
+        assert_eq!(container.block_type(), BlockType::Adaptive);
+        assert_eq!(container.decode().unwrap(), text);
+
+        let data = [0x00u8, 0xFF, 0x10, 0x00, 0x00];
+        let bytes = write_adaptive_container::<u8>(&data);
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(container.decode_symbols().unwrap(), data);
+    }
+
+    #[test]
+    fn test_adaptive_container_detects_corruption() {
+        let text = "some adaptive text";
+        let mut bytes = write_adaptive_container::<char>(text.as_bytes());
+        let last = bytes.len() - 1;
+        bytes.truncate(last);
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::Truncated
+        );
+
+        let mut bytes = write_adaptive_container::<char>(text.as_bytes());
+        // Flip a bit in the checksum so the payload still decodes.
+        bytes[CONTAINER_PREFIX_LEN] ^= 1;
+        assert!(matches!(
+            read_container::<char>(&bytes).unwrap().decode(),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_empty_container_round_trip() {
+        let bytes = write_container::<char>(&HashMap::new(), &[], 0, crc32(b""));
+        let container = read_container::<char>(&bytes).unwrap();
+        assert_eq!(container.block_type(), BlockType::Huffman);
+        assert_eq!(container.decode().unwrap(), "");
+
+        // An empty table that claims symbols is still rejected.
+        let bytes = write_container::<char>(&HashMap::new(), &[], 3, crc32(b""));
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::EmptyTable
+        );
+    }
+
+    #[test]
+    fn test_container_rejects_unknown_block_type() {
+        let mut bytes = write_stored_container::<char>(b"abc");
+        bytes[6] = 4;
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::UnknownBlockType(4)
+        );
+    }
+
+    #[test]
+    fn test_container_rejects_bad_magic() {
+        let mut bytes = write_container(&sample_frequencies(), &[], 0, 0);
+        bytes[0] = b'X';
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::BadMagic
+        );
+    }
+
+    #[test]
+    fn test_container_rejects_unknown_version() {
+        let mut bytes = write_container(&sample_frequencies(), &[], 0, 0);
+        bytes[4] = FORMAT_VERSION + 1;
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::UnsupportedVersion(FORMAT_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn test_rebuilt_tree_matches_original_table() {
+        let mut frequencies = sample_frequencies();
+        // Equal counts force the tree builder to break ties.
+        frequencies.insert('e', 2);
+        frequencies.insert('f', 1);
+        frequencies.insert('g', 1);
+
+        let original = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        let restored: HashMap<char, u64> =
+            deserialize_frequencies(&serialize_frequencies(&frequencies)).unwrap();
+        assert_eq!(restored, frequencies);
+
+        let rebuilt = HuffmanTree::build_tree(&restored)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(rebuilt, original);
+    }
+
+    #[test]
+    fn test_table_round_trip_random_frequencies() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..50 {
+            let symbol_count = 1 + rng.next() % 40;
+            let mut frequencies = HashMap::new();
+            while frequencies.len() < symbol_count as usize {
+                // Mix ASCII with multi-byte code points to exercise UTF-8 widths.
+                let value = match rng.next() % 3 {
+                    0 => 0x20 + rng.next() % 0x5F,
+                    1 => 0xA0 + rng.next() % 0x700,
+                    _ => 0x1F300 + rng.next() % 0x300,
+                };
+                let c = char::from_u32(value as u32).unwrap();
+                frequencies.insert(c, 1 + rng.next() % 1000);
+            }
+
+            let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+            let code = HuffmanCode::new(tree.build_encoding_table());
+            let restored = HuffmanCode::deserialize_codes(&code.serialize_codes()).unwrap();
+            assert_eq!(restored.encoding_table, code.encoding_table);
+        }
+    }
+
+    #[test]
+    fn test_truncated_input_is_rejected_without_panicking() {
+        let text = "truncate ünïcödé 🎉 text\n".repeat(4);
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode(&text).unwrap();
+        let container = write_container(
+            &frequencies,
+            &bits,
+            text.chars().count() as u64,
+            crc32(text.as_bytes()),
+        );
+        let lengths = code.serialize();
+        let table = code.serialize_codes();
+        let frequency_table = serialize_frequencies(&frequencies);
+
+        for len in 0..container.len() {
+            assert!(read_container::<char>(&container[..len]).is_err());
+        }
+        for len in 0..lengths.len() {
+            assert!(HuffmanCode::<char>::deserialize(&lengths[..len]).is_err());
+        }
+        for len in 0..table.len() {
+            assert!(HuffmanCode::<char>::deserialize_codes(&table[..len]).is_err());
+        }
+        for len in 0..frequency_table.len() {
+            assert!(deserialize_frequencies::<char>(&frequency_table[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_corrupted_input_does_not_panic() {
+        let text = "corrupt me, ça va? 🎉".repeat(3);
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode(&text).unwrap();
+        let inputs = [
+            write_container(
+                &frequencies,
+                &bits,
+                text.chars().count() as u64,
+                crc32(text.as_bytes()),
+            ),
+            code.serialize(),
+            code.serialize_codes(),
+        ];
+
+        let mut rng = XorShift(0xDEAD_BEEF_CAFE_F00D);
+        for _ in 0..2000 {
+            let mut data = inputs[(rng.next() % 3) as usize].clone();
+            for _ in 0..1 + rng.next() % 4 {
+                let i = (rng.next() % data.len() as u64) as usize;
+                data[i] = rng.next() as u8;
+            }
+            let len = (rng.next() % (data.len() as u64 + 1)) as usize;
+            let data = &data[..len];
+
+            // Any outcome but a panic is fine here.
+            if let Ok(container) = read_container::<char>(data) {
+                let _ = container.decode();
+            }
+            let _ = HuffmanCode::<char>::deserialize(data);
+            let _ = HuffmanCode::<char>::deserialize_codes(data);
+            let _ = HuffmanCode::<u8>::deserialize_codes(data);
+        }
+    }
+
+    #[test]
+    fn test_frequencies_overflowing_a_count_are_rejected() {
+        let mut data = 2u32.to_be_bytes().to_vec();
+        data.push(b'a');
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        data.push(b'b');
+        data.extend_from_slice(&1u64.to_be_bytes());
+        assert_eq!(
+            deserialize_frequencies::<char>(&data).unwrap_err(),
+            FormatError::FrequencyOverflow
+        );
+    }
+
+    fn round_trip(input: &str) -> (Vec<bool>, String) {
+        let mut frequencies = HashMap::new();
+        for c in input.chars() {
+            *frequencies.entry(c).or_insert(0) += 1;
+        }
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode(input).unwrap();
+        let decoded = code.decode(&bits).unwrap();
+        (bits, decoded)
+    }
+
+    #[test]
+    fn test_crlf_round_trips_byte_identical() {
+        let input = "a\r\nb\r\n";
+        let code = HuffmanCode::from_reader(Cursor::new(input.as_bytes())).unwrap();
+        assert_eq!(code.code_lengths().len(), 4);
+
+        let bits = code.encode(input).unwrap();
+        assert_eq!(code.decode(&bits).unwrap().as_bytes(), input.as_bytes());
+
+        let mut compressed = Vec::new();
+        let bit_len = code
+            .encode_stream(Cursor::new(input.as_bytes()), &mut compressed)
+            .unwrap();
+        let mut decompressed = Vec::new();
+        code.decode_stream(Cursor::new(&compressed), &mut decompressed, bit_len)
+            .unwrap();
+        assert_eq!(decompressed, input.as_bytes());
+    }
+
+    #[test]
+    fn test_single_distinct_symbol_round_trip() {
+        let (bits, decoded) = round_trip("aaaa");
+        assert_eq!(bits, vec![false; 4]);
+        assert_eq!(decoded, "aaaa");
+    }
+
+    #[test]
+    fn test_single_char_round_trip() {
+        let (bits, decoded) = round_trip("a");
+        assert_eq!(bits, vec![false]);
+        assert_eq!(decoded, "a");
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let data: Vec<u8> = (0..=255u8).chain([0x00, 0x00, 0xFF, 0xFF, 0xFF]).collect();
+        let mut frequencies = HashMap::new();
+        for &byte in &data {
+            *frequencies.entry(byte).or_insert(0) += 1;
+        }
+
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode_symbols(data.iter().copied()).unwrap();
+        let bytes = write_container(&frequencies, &bits, data.len() as u64, crc32(&data));
+
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(container.decode_symbols().unwrap(), data);
+    }
+
+    #[test]
+    fn test_u16_round_trip() {
+        // UTF-16 text: a few common code units and one surrogate pair.
+        let symbols: Vec<u16> = "hello wörld, hello 🎉 ".repeat(20).encode_utf16().collect();
+        let data = compress_symbols(&symbols, &EncodeOptions::default());
+        assert_eq!(data[5], u16::KIND);
+        assert!(data.len() < symbols.len() * 2);
+
+        let container = read_container::<u16>(&data).unwrap();
+        assert_eq!(container.decode_symbols().unwrap(), symbols);
+        assert_eq!(
+            read_container::<u8>(&data).unwrap_err(),
+            FormatError::SymbolKindMismatch(u16::KIND)
+        );
+    }
+
+    #[test]
+    fn test_container_rejects_symbol_kind_mismatch() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert(0xFFu8, 1);
+        let bytes = write_container(&frequencies, &[false], 1, 0);
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::SymbolKindMismatch(u8::KIND)
+        );
+    }
+
+    fn code_for(input: &str) -> HuffmanCode {
+        let mut frequencies = HashMap::new();
+        for c in input.chars() {
+            *frequencies.entry(c).or_insert(0) += 1;
+        }
+        HuffmanCode::from_frequencies(&frequencies).unwrap()
+    }
+
+    #[test]
+    fn test_encode_stream_matches_in_memory_encode() {
+        // Long enough to span several chunks, with multi-byte chars that
+        // end up split across chunk boundaries.
+        let input = "héllo wörld, ça va? ".repeat(1000);
+        let code = code_for(&input);
+
+        let mut output = Vec::new();
+        let bit_len = code
+            .encode_stream(Cursor::new(input.as_bytes()), &mut output)
+            .unwrap();
+
+        let bits = code.encode(&input).unwrap();
+        assert_eq!(bit_len, bits.len() as u64);
+        assert_eq!(output, pack_bits(&bits));
+    }
+
+    #[test]
+    fn test_packed_and_long_codes_produce_identical_bitstreams() {
+        // Fibonacci counts give a maximally skewed tree, so the rarest
+        // symbols get codes longer than 64 bits and use the fallback.
+        let mut frequencies = HashMap::new();
+        let (mut a, mut b) = (1u64, 1u64);
+        for c in ('A'..='Z')
+            .chain('a'..='z')
+            .chain('0'..='9')
+            .chain("+-*/!?".chars())
+        {
+            frequencies.insert(c, a);
+            (a, b) = (b, a + b);
+        }
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        assert!(code.max_code_len() > 64);
+        assert!(matches!(code.encoding_table[&'A'], PackedCode::Long(_)));
+        assert!(matches!(
+            code.encoding_table[&'?'],
+            PackedCode::Short { .. }
+        ));
+
+        let table = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        let input: String = "?!?AB0az/?".repeat(50);
+        let expected: Vec<bool> = input.chars().flat_map(|c| table[&c].clone()).collect();
+        assert_eq!(code.encode(&input).unwrap(), expected);
+        assert_eq!(code.decode(&expected).unwrap(), input);
+
+        let mut output = Vec::new();
+        code.encode_stream(Cursor::new(input.as_bytes()), &mut output)
+            .unwrap();
+        assert_eq!(output, pack_bits(&expected));
+        for (c, bits) in code.codes_sorted() {
+            assert_eq!(bits, table[&c]);
+        }
+    }
+
+    #[test]
+    fn test_encode_stream_rejects_unknown_symbol() {
+        let code = sample_code();
+        let err = code
+            .encode_stream(Cursor::new("abz".as_bytes()), Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_block_size_does_not_change_output() {
+        let input = "blocks of ünïcödé text 🎉\n".repeat(3000);
+        let code = code_for(&input);
+
+        let outputs: Vec<_> = [1, 3, 1000, EncodeOptions::default().block_size]
+            .into_iter()
+            .map(|block_size| {
+                let mut output = Vec::new();
+                let bit_len = code
+                    .encode_stream_with_options(
+                        Cursor::new(input.as_bytes()),
+                        &mut output,
+                        &EncodeOptions {
+                            block_size,
+                            ..EncodeOptions::default()
+                        },
+                    )
+                    .unwrap();
+                (bit_len, output)
+            })
+            .collect();
+
+        assert!(outputs.windows(2).all(|w| w[0] == w[1]));
+        assert_eq!(outputs[0].1, pack_bits(&code.encode(&input).unwrap()));
+    }
+
+    #[test]
+    fn test_bit_orders_round_trip_with_different_bytes() {
+        let text = "bit order test: ünï 🎉 ".repeat(50);
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let symbols: Vec<char> = text.chars().collect();
+
+        let mut streams = Vec::new();
+        let mut containers = Vec::new();
+        for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let options = EncodeOptions {
+                bit_order,
+                ..EncodeOptions::default()
+            };
+            let mut stream = Vec::new();
+            let bit_len = code
+                .encode_stream_with_options(Cursor::new(text.as_bytes()), &mut stream, &options)
+                .unwrap();
+            let mut decoded = Vec::new();
+            code.decode_stream_with_order(&stream[..], &mut decoded, bit_len, bit_order)
+                .unwrap();
+            assert_eq!(decoded, text.as_bytes());
+
+            let container =
+                write_compressed_container_with_options(&frequencies, &code, &symbols, &options)
+                    .unwrap();
+            assert_eq!(container[CONTAINER_PREFIX_LEN], bit_order as u8);
+            assert_eq!(
+                read_container::<char>(&container)
+                    .unwrap()
+                    .decode()
+                    .unwrap(),
+                text
+            );
+
+            streams.push(stream);
+            containers.push(container);
+        }
+        assert_ne!(streams[0], streams[1]);
+        assert_ne!(containers[0], containers[1]);
+        assert_eq!(containers[0].len(), containers[1].len());
+        assert_eq!(
+            containers[0],
+            write_compressed_container(&frequencies, &code, &symbols).unwrap()
+        );
+
+        let mut bytes = containers[0].clone();
+        bytes[CONTAINER_PREFIX_LEN] = 2;
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::UnknownBitOrder(2)
+        );
+    }
+
+    #[test]
+    fn test_control_chars_round_trip_through_tables() {
+        let text = "\0nul\x01soh\x7fdel\0\0\x01\t\r\n".repeat(30);
+        let symbols: Vec<char> = text.chars().collect();
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        // Control chars are one UTF-8 byte each, like any other ASCII char.
+        let table = serialize_frequencies(&frequencies);
+        assert_eq!(table.len(), 4 + frequencies.len() * 9);
+        assert_eq!(
+            deserialize_frequencies::<char>(&table).unwrap(),
+            frequencies
+        );
+        let codes = HuffmanCode::<char>::deserialize_codes(&code.serialize_codes()).unwrap();
+        assert_eq!(codes.codes_sorted(), code.codes_sorted());
+        let lengths = HuffmanCode::<char>::deserialize(&code.serialize()).unwrap();
+        assert_eq!(lengths.code_lengths(), code.code_lengths());
+        for c in ['\0', '\x01', '\x7f'] {
+            assert!(lengths.code_lengths().contains_key(&c), "{:?}", c);
+        }
+
+        for table_format in [
+            TableFormat::Frequencies,
+            TableFormat::Codes,
+            TableFormat::CanonicalLengths,
+        ] {
+            let options = EncodeOptions {
+                table_format,
+                ..EncodeOptions::default()
+            };
+            let container =
+                write_compressed_container_with_options(&frequencies, &code, &symbols, &options)
+                    .unwrap();
+            let container = read_container::<char>(&container).unwrap();
+            assert_eq!(container.block_type(), BlockType::Huffman);
+            assert_eq!(container.decode().unwrap(), text);
+        }
+        let stored = read_container::<char>(&write_stored_container::<char>(text.as_bytes()));
+        assert_eq!(stored.unwrap().decode().unwrap(), text);
+    }
+
+    #[test]
+    fn test_table_formats_round_trip() {
+        let text = "the table format decides what the header stores\n".repeat(40);
+        let symbols: Vec<char> = text.chars().collect();
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let mut lens = Vec::new();
+        for table_format in [
+            TableFormat::Frequencies,
+            TableFormat::Codes,
+            TableFormat::CanonicalLengths,
+        ] {
+            let options = EncodeOptions {
+                table_format,
+                ..EncodeOptions::default()
+            };
+            let container =
+                write_compressed_container_with_options(&frequencies, &code, &symbols, &options)
+                    .unwrap();
+            assert_eq!(container[CONTAINER_PREFIX_LEN + 1], table_format as u8);
+            let restored = read_container::<char>(&container).unwrap();
+            assert_eq!(restored.decode().unwrap(), text);
+            if let Container::Huffman { code: restored, .. } = restored {
+                assert_eq!(restored.code_lengths(), code.code_lengths());
+            } else {
+                panic!("expected a Huffman container");
+            }
+            lens.push(container.len());
+        }
+
+        // Every format codes the payload in the same number of bits, so the
+        // sizes differ only by the table: 8-byte counts, packed codes, then
+        // 1-byte lengths.
+        assert!(lens[0] > lens[1], "{:?}", lens);
+        assert!(lens[1] > lens[2], "{:?}", lens);
+        assert_eq!(lens[0] - lens[2], frequencies.len() * 7);
+        assert_eq!(
+            write_compressed_container(&frequencies, &code, &symbols).unwrap()
+                [CONTAINER_PREFIX_LEN + 1],
+            TableFormat::Frequencies as u8
+        );
+    }
+
+    #[test]
+    fn test_container_rejects_bad_tables() {
+        let text = "abracadabra".repeat(10);
+        let symbols: Vec<char> = text.chars().collect();
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let options = EncodeOptions {
+            table_format: TableFormat::CanonicalLengths,
+            ..EncodeOptions::default()
+        };
+        let container =
+            write_compressed_container_with_options(&frequencies, &code, &symbols, &options)
+                .unwrap();
+
+        let mut bytes = container.clone();
+        bytes[CONTAINER_PREFIX_LEN + 1] = 3;
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::UnknownTableFormat(3)
+        );
+
+        // Five 1-bit codes do not fit in the code space. Each entry is an
+        // ASCII symbol and its length.
+        let mut bytes = container;
+        let entries = CONTAINER_PREFIX_LEN + 2 + 4;
+        for i in 0..frequencies.len() {
+            bytes[entries + 2 * i + 1] = 1;
+        }
+        assert_eq!(
+            read_container::<char>(&bytes).unwrap_err(),
+            FormatError::NotPrefixCode
+        );
+    }
+
+    #[test]
+    fn test_decode_lossy_replaces_invalid_utf8() {
+        let data = b"caf\xC3\xA9 \xFF\xFEok \xE2\x82";
+        let frequencies = count_symbols(data.iter().copied());
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode_symbols(data.iter().copied()).unwrap();
+
+        let text = code.decode_lossy(&bits).unwrap();
+        assert_eq!(text, "café \u{FFFD}\u{FFFD}ok \u{FFFD}");
+        assert_eq!(text, String::from_utf8_lossy(data));
+        assert_eq!(
+            code.decode_lossy(&bits[..bits.len() - 1]),
+            Err(DecodeError::InvalidBitstream)
+        );
+
+        let container = write_compressed_container(&frequencies, &code, data).unwrap();
+        let container = read_container::<u8>(&container).unwrap();
+        assert_eq!(container.decode_lossy().unwrap(), text);
+    }
+
+    #[test]
+    fn test_sampled_model_handles_late_symbols() {
+        let head = "sampled head of the input ".repeat(40);
+        let sampled = EncodeOptions {
+            sample_bytes: Some(100),
+            ..EncodeOptions::default()
+        };
+
+        // Everything after the sample uses symbols it already saw.
+        let symbols: Vec<char> = head.chars().collect();
+        let container = read_container::<char>(&compress_symbols(&symbols, &sampled)).unwrap();
+        assert_eq!(container.decode().unwrap(), head);
+        match &container {
+            Container::Huffman { code, .. } => {
+                let sample: String = head.chars().take(100).collect();
+                assert_eq!(code.code_lengths().len(), count_chars(&sample).len());
+            }
+            _ => panic!("expected a Huffman container"),
+        }
+
+        // A symbol that first shows up later falls back to a stored block.
+        let text = format!("{}🎉 and a late Z", head);
+        let symbols: Vec<char> = text.chars().collect();
+        let bytes = compress_symbols(&symbols, &sampled);
+        let container = read_container::<char>(&bytes).unwrap();
+        assert_eq!(container.block_type(), BlockType::Stored);
+        assert_eq!(container.decode().unwrap(), text);
+
+        // Without a sample the whole input is counted.
+        let container =
+            read_container::<char>(&compress_symbols(&symbols, &EncodeOptions::default())).unwrap();
+        assert_eq!(container.block_type(), BlockType::Huffman);
+        assert_eq!(container.decode().unwrap(), text);
+
+        for options in [EncodeOptions::default(), sampled] {
+            let empty = read_container::<u8>(&compress_symbols::<u8>(&[], &options)).unwrap();
+            assert_eq!(empty.decode_symbols().unwrap(), Vec::<u8>::new());
+        }
+        let zero = EncodeOptions {
+            sample_bytes: Some(0),
+            ..EncodeOptions::default()
+        };
+        let bytes = compress_symbols(b"abc", &zero);
+        assert_eq!(
+            read_container::<u8>(&bytes)
+                .unwrap()
+                .decode_symbols()
+                .unwrap(),
+            b"abc"
+        );
+    }
+
+    #[test]
+    fn test_run_length_transform_helps_long_runs() {
+        let rle = EncodeOptions {
+            transform: Some(Transform::RunLength),
+            ..EncodeOptions::default()
+        };
+        let text = "a".repeat(2000) + &"b".repeat(2000) + &"a".repeat(500);
+        let symbols: Vec<char> = text.chars().collect();
+        let plain = compress_symbols(&symbols, &EncodeOptions::default());
+        let transformed = compress_symbols(&symbols, &rle);
+        assert!(
+            transformed.len() * 4 < plain.len(),
+            "rle {} bytes, plain {} bytes",
+            transformed.len(),
+            plain.len()
+        );
+
+        let container = read_container::<char>(&transformed).unwrap();
+        assert_eq!(container.block_type(), BlockType::Transformed);
+        assert_eq!(container.decode().unwrap(), text);
+
+        for text in ["aaaaaabbbbbb", "", "no runs", "ünïcödé ääää"] {
+            let symbols: Vec<char> = text.chars().collect();
+            let container = read_container::<char>(&compress_symbols(&symbols, &rle)).unwrap();
+            assert_eq!(container.decode().unwrap(), text);
+            assert_eq!(container.decode_symbols().unwrap(), symbols);
+        }
+        let bytes = compress_symbols(b"\x00\x00\x00\xFF", &rle);
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(container.decode_symbols().unwrap(), b"\x00\x00\x00\xFF");
+    }
+
+    #[test]
+    fn test_move_to_front_transform_round_trips_through_huffman() {
+        let mtf = EncodeOptions {
+            transform: Some(Transform::MoveToFront),
+            ..EncodeOptions::default()
+        };
+        // Runs of many different bytes: every byte gets its own code
+        // without the transform, but mostly turns into zeros with it.
+        let data: Vec<u8> = (0..=255u8).flat_map(|b| [b; 64]).collect();
+        let plain = compress_symbols(&data, &EncodeOptions::default());
+        let transformed = compress_symbols(&data, &mtf);
+        assert!(
+            transformed.len() * 2 < plain.len(),
+            "mtf {} bytes, plain {} bytes",
+            transformed.len(),
+            plain.len()
+        );
+        let container = read_container::<u8>(&transformed).unwrap();
+        assert!(matches!(
+            container,
+            Container::Transformed {
+                transform: Transform::MoveToFront,
+                ..
+            }
+        ));
+        assert_eq!(container.decode_symbols().unwrap(), data);
+
+        let text = "ünïcödé text, moved to the front 🎉".repeat(20);
+        let symbols: Vec<char> = text.chars().collect();
+        let container = read_container::<char>(&compress_symbols(&symbols, &mtf)).unwrap();
+        assert_eq!(container.decode().unwrap(), text);
+    }
+
+    #[test]
+    fn test_transformed_container_rejects_nesting_and_bad_transforms() {
+        let inner = compress_symbols(b"abb", &EncodeOptions::default());
+        let bytes = write_transformed_container::<u8>(Transform::RunLength, 0, &inner);
+        let nested = write_transformed_container::<u8>(Transform::RunLength, 0, &bytes);
+        assert_eq!(
+            read_container::<u8>(&nested).unwrap_err(),
+            FormatError::NestedTransform
+        );
+
+        let mut unknown = bytes.clone();
+        unknown[CONTAINER_PREFIX_LEN] = 0xEE;
+        assert_eq!(
+            read_container::<u8>(&unknown).unwrap_err(),
+            FormatError::UnknownTransform(0xEE)
+        );
+
+        // "abb" is not the run-length form of anything: the pair has no
+        // count.
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(
+            container.decode_symbols(),
+            Err(DecodeError::InvalidBitstream)
+        );
+    }
+
+    #[test]
+    fn test_max_output_bytes_stops_large_payloads() {
+        let text = "a highly compressible payload ".repeat(1000);
+        let symbols: Vec<char> = text.chars().collect();
+        let limited = DecodeOptions {
+            max_output_bytes: Some(1000),
+        };
+        let rle = EncodeOptions {
+            transform: Some(Transform::RunLength),
+            ..EncodeOptions::default()
+        };
+        let containers = [
+            compress_symbols(&symbols, &EncodeOptions::default()),
+            write_adaptive_container::<char>(text.as_bytes()),
+            write_stored_container::<char>(text.as_bytes()),
+            compress_symbols(&symbols, &rle),
+        ];
+        for bytes in &containers {
+            let container = read_container::<char>(bytes).unwrap();
+            assert_eq!(
+                container.decode_with_options(&limited),
+                Err(DecodeError::OutputLimitExceeded { limit: 1000 }),
+                "{:?}",
+                container.block_type()
+            );
+            assert_eq!(
+                container.decode_symbols_with_options(&limited),
+                Err(DecodeError::OutputLimitExceeded { limit: 1000 })
+            );
+            let exact = DecodeOptions {
+                max_output_bytes: Some(text.len() as u64),
+            };
+            assert_eq!(container.decode_with_options(&exact).unwrap(), text);
+        }
+
+        // Fewer chars than the limit can still be more bytes. The bits stop
+        // after 400 of the 500 chars the header claims, so only a decoder
+        // that stops at the limit reports it instead of a short bitstream.
+        let frequencies = HashMap::from([('€', 499), ('é', 1)]);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let text = "€".repeat(400);
+        let bits = code.encode(&text).unwrap();
+        let euros = write_container(&frequencies, &bits, 500, crc32(text.as_bytes()));
+        let container = read_container::<char>(&euros).unwrap();
+        assert_eq!(
+            container.decode_with_options(&limited),
+            Err(DecodeError::OutputLimitExceeded { limit: 1000 })
+        );
+        assert_eq!(
+            container.decode_symbols_with_options(&limited),
+            Err(DecodeError::OutputLimitExceeded { limit: 1000 })
+        );
+        assert_eq!(container.decode(), Err(DecodeError::InvalidBitstream));
+
+        // A run of zeros shrinks below the limit but is still caught.
+        let zeros = vec![0u8; 100_000];
+        let bytes = compress_symbols(&zeros, &rle);
+        assert!(bytes.len() < 1000, "{} bytes", bytes.len());
+        let container = read_container::<u8>(&bytes).unwrap();
+        assert_eq!(
+            container.decode_symbols_with_options(&limited),
+            Err(DecodeError::OutputLimitExceeded { limit: 1000 })
+        );
+    }
+
+    #[test]
+    fn test_encode_lines_matches_full_buffer_encode() {
+        let text = "first line\nsecond line\r\n\nünïcödé 🎉\nno newline at the end";
+        let code = code_for(text);
+        let bits = code.encode(text).unwrap();
+
+        let mut encoded = Vec::new();
+        let bit_len = code.encode_lines(Cursor::new(text), &mut encoded).unwrap();
+        assert_eq!(bit_len, bits.len() as u64);
+        assert_eq!(encoded, pack_bits(&bits));
+
+        let mut chunked = Vec::new();
+        code.encode_stream(Cursor::new(text), &mut chunked).unwrap();
+        assert_eq!(encoded, chunked);
+
+        let err = code
+            .encode_lines(Cursor::new("unknown: Z\n"), Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_encode_stream_reports_progress() {
+        let input = "progress ".repeat(5000);
+        let code = code_for(&input);
+
+        let mut calls = Vec::new();
+        code.encode_stream_with_progress(
+            Cursor::new(input.as_bytes()),
+            Vec::new(),
+            &EncodeOptions {
+                block_size: 4096,
+                ..EncodeOptions::default()
+            },
+            input.len() as u64,
+            |processed, total| calls.push((processed, total)),
+        )
+        .unwrap();
+
+        assert!(calls.len() > 1);
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(calls.iter().all(|&(_, total)| total == input.len() as u64));
+        assert_eq!(calls.last().unwrap().0, input.len() as u64);
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let input: String = (0..5000)
+            .map(|i| ['a', 'b', 'c', 'é', '\n', '🎉'][i * 7 % 6])
+            .collect();
+        let code = code_for(&input);
+
+        let mut compressed = Vec::new();
+        let bit_len = code
+            .encode_stream(Cursor::new(input.as_bytes()), &mut compressed)
+            .unwrap();
+
+        let mut decompressed = Vec::new();
+        code.decode_stream(Cursor::new(&compressed), &mut decompressed, bit_len)
+            .unwrap();
+        assert_eq!(String::from_utf8(decompressed).unwrap(), input);
+    }
+
+    #[test]
+    fn test_decode_stream_reports_truncated_input() {
+        let code = sample_code();
+        let err = code
+            .decode_stream(Cursor::new(vec![0u8]), Vec::new(), 16)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    fn bits_from_str(s: &str) -> Vec<bool> {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn test_canonical_codes() {
+        let lengths: HashMap<char, u8> = [
+            ('a', 3),
+            ('b', 3),
+            ('c', 3),
+            ('d', 3),
+            ('e', 3),
+            ('f', 2),
+            ('g', 4),
+            ('h', 4),
+        ]
+        .into_iter()
+        .collect();
+
+        let code = HuffmanCode::canonical(&lengths);
+        let expected = [
+            ('f', "00"),
+            ('a', "010"),
+            ('b', "011"),
+            ('c', "100"),
+            ('d', "101"),
+            ('e', "110"),
+            ('g', "1110"),
+            ('h', "1111"),
+        ];
+        assert_eq!(code.encoding_table.len(), expected.len());
+        for (c, bits) in expected {
+            assert_eq!(
+                code.encoding_table[&c].to_bits(),
+                bits_from_str(bits),
+                "code for {:?}",
+                c
+            );
+        }
+
+        let bits = code.encode("fadeh").unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), "fadeh");
+    }
+
+    #[test]
+    fn test_serialize_lengths_round_trip() {
+        let input = "the quick brown fox jumps over the lazy dog, now in canonical form";
+        let code = code_for(input);
+        let canonical = HuffmanCode::canonical(&code.code_lengths());
+        assert_eq!(canonical.code_lengths(), code.code_lengths());
+
+        let bytes = canonical.serialize();
+        let restored = HuffmanCode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.encoding_table, canonical.encoding_table);
+
+        let bits = canonical.encode(input).unwrap();
+        assert_eq!(restored.decode(&bits).unwrap(), input);
+
+        assert!(
+            bytes.len() < canonical.serialize_codes().len(),
+            "lengths-only table ({} bytes) should be smaller than the full table ({} bytes)",
+            bytes.len(),
+            canonical.serialize_codes().len()
+        );
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let input = "from_reader builds the whole code in one call\nincluding newlines\n";
+        let code = HuffmanCode::from_reader(Cursor::new(input)).unwrap();
+
+        let bits = code.encode(input).unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), input);
+        assert_eq!(code.code_lengths(), code_for(input).code_lengths());
+    }
+
+    #[test]
+    fn test_from_reader_empty_input() {
+        assert!(matches!(
+            HuffmanCode::from_reader(Cursor::new("")),
+            Err(HuffmanError::EmptyInput)
+        ));
+        match HuffmanCode::from_reader(Cursor::new(b"\xFF".to_vec())) {
+            Err(HuffmanError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            other => panic!("expected an InvalidData error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_frequencies() {
+        let code = HuffmanCode::from_frequencies(&sample_frequencies()).unwrap();
+
+        assert_eq!(code.encoding_table.len(), 4);
+        assert_eq!(code.encoding_table[&'d'].to_bits(), vec![false]);
+        assert_eq!(code.encoding_table[&'a'].to_bits(), vec![true, true]);
+        assert_eq!(code.encoding_table[&'b'].to_bits(), vec![true, false, true]);
+        assert_eq!(
+            code.encoding_table[&'c'].to_bits(),
+            vec![true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_from_frequencies_empty() {
+        let frequencies: HashMap<char, u64> = HashMap::new();
+        assert_eq!(
+            HuffmanCode::from_frequencies(&frequencies).unwrap_err(),
+            BuildError::EmptyFrequencies
+        );
+    }
+
+    #[test]
+    fn test_multi_byte_chars_round_trip() {
+        let input = "café 🎉";
+        let frequencies = count_chars(input);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let bits = code.encode(input).unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), input);
+
+        let container = read_container::<char>(&write_container(
+            &frequencies,
+            &bits,
+            input.chars().count() as u64,
+            crc32(input.as_bytes()),
+        ))
+        .unwrap();
+        assert_eq!(container.decode().unwrap(), input);
+
+        let restored = HuffmanCode::deserialize_codes(&code.serialize_codes()).unwrap();
+        assert_eq!(restored.encoding_table, code.encoding_table);
+    }
+
+    #[test]
+    fn test_deserialize_codes_rejects_ambiguous_table() {
+        // 'a' = 1 and 'b' = 10: the code of 'a' is a prefix of 'b'.
+        let mut data = 2u32.to_be_bytes().to_vec();
+        data.extend_from_slice(&[b'a', 1, 0b1000_0000]);
+        data.extend_from_slice(&[b'b', 2, 0b1000_0000]);
+        assert_eq!(
+            HuffmanCode::<char>::deserialize_codes(&data).unwrap_err(),
+            FormatError::NotPrefixCode
+        );
+    }
+
+    #[test]
+    fn test_serialization_is_deterministic() {
+        let text = "the same input must give the same bytes, every time";
+        let frequencies = count_chars(text);
+        // A map with the same entries, but built separately, so its
+        // iteration order is unrelated to the first one's.
+        let frequencies_again: HashMap<char, u64> = {
+            let mut entries: Vec<_> = frequencies.iter().map(|(&c, &n)| (c, n)).collect();
+            entries.reverse();
+            entries.into_iter().collect()
+        };
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let code_again = HuffmanCode::from_frequencies(&frequencies_again).unwrap();
+
+        assert_eq!(code.serialize(), code_again.serialize());
+        assert_eq!(code.serialize_codes(), code_again.serialize_codes());
+        assert_eq!(
+            serialize_frequencies(&frequencies),
+            serialize_frequencies(&frequencies_again)
+        );
+        assert_eq!(code.serialize(), code.serialize());
+
+        // Entries come in symbol order: ' ' is the smallest char here.
+        let bytes = serialize_frequencies(&frequencies);
+        assert_eq!(bytes[4], b' ');
+    }
+
+    #[test]
+    fn test_encoder_matches_separate_encode_calls() {
+        let messages = ["hello", "", "world", "held low", "x"];
+        let frequencies = count_chars("hello world");
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let mut encoder = Encoder::new(HuffmanCode::from_frequencies(&frequencies).unwrap());
+
+        let mut out = Vec::new();
+        let mut expected = Vec::new();
+        for message in &messages[..4] {
+            let bits = code.encode(message).unwrap();
+            assert_eq!(encoder.encode(message).unwrap(), bits);
+            encoder.encode_into(message, &mut out).unwrap();
+            expected.extend(bits);
+        }
+        assert_eq!(out, expected);
+
+        // A message with an unknown char leaves the output untouched.
+        assert_eq!(
+            encoder.encode_into(messages[4], &mut out),
+            Err(EncodeError::UnknownSymbol('x'))
+        );
+        assert_eq!(out, expected);
+        assert!(encoder.encode("hex").is_err());
+        assert_eq!(encoder.encode("he").unwrap(), code.encode("he").unwrap());
+    }
+
+    #[test]
+    fn test_code_lengths_array_is_indexed_by_byte() {
+        // The example from RFC 1951, section 3.2.2: lengths (3, 3, 3, 3, 3,
+        // 2, 4, 4) for symbols A to H.
+        let lengths: HashMap<u8, u8> = (0..8).zip([3, 3, 3, 3, 3, 2, 4, 4]).collect();
+        let code = HuffmanCode::canonical(&lengths);
+        assert_eq!(
+            code.code_lengths_array(9),
+            vec![3, 3, 3, 3, 3, 2, 4, 4, 0, 0]
+        );
+        assert_eq!(code.codes_sorted()[0], (5, vec![false, false]));
+
+        let frequencies = HashMap::from([(b'a', 8), (b'c', 4), (0xFF, 2), (0x00, 2)]);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let array = code.code_lengths_array(255);
+        assert_eq!(array.len(), 256);
+        assert_eq!((array[b'a' as usize], array[b'c' as usize]), (1, 2));
+        assert_eq!((array[0x00], array[0xFF]), (3, 3));
+        assert_eq!(array.iter().filter(|&&len| len > 0).count(), 4);
+        assert_eq!(
+            HuffmanCode::<char>::new(HashMap::new()).code_lengths_array(3),
+            vec![0; 4]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "above max_symbol")]
+    fn test_code_lengths_array_rejects_small_max_symbol() {
+        let code = HuffmanCode::canonical(&HashMap::from([(b'a', 1), (b'z', 1)]));
+        code.code_lengths_array(b'a' as usize);
+    }
+
+    #[test]
+    fn test_assigns_same_lengths() {
+        let frequencies = count_chars("abracadabra, a canonical comparison");
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let canonical = HuffmanCode::canonical(&code.code_lengths());
+        assert!(code.assigns_same_lengths(&canonical));
+        assert!(canonical.assigns_same_lengths(&code));
+        assert_ne!(code.codes_sorted(), canonical.codes_sorted());
+
+        // A different length for one symbol, or a missing symbol, differs.
+        let a = HuffmanCode::new(HashMap::from([('a', vec![false]), ('b', vec![true])]));
+        let longer = HuffmanCode::new(HashMap::from([
+            ('a', vec![false]),
+            ('b', vec![true, false]),
+        ]));
+        let other_symbol = HuffmanCode::new(HashMap::from([('a', vec![true]), ('c', vec![false])]));
+        assert!(!a.assigns_same_lengths(&longer));
+        assert!(!a.assigns_same_lengths(&other_symbol));
+        assert!(!a.assigns_same_lengths(&HuffmanCode::new(HashMap::new())));
+        assert!(HuffmanCode::<char>::new(HashMap::new())
+            .assigns_same_lengths(&HuffmanCode::new(HashMap::new())));
+    }
+
+    #[test]
+    fn test_satisfies_kraft() {
+        assert!(sample_code().satisfies_kraft());
+        let code = HuffmanCode::from_frequencies(&count_chars("some text for a code")).unwrap();
+        assert!(code.satisfies_kraft());
+
+        // A chain of codes 0, 10, 110, ... fills the tree exactly, even past
+        // 64 bits.
+        let chain: HashMap<u8, Vec<bool>> = (0..=70u8)
+            .map(|i| {
+                let mut bits = vec![true; i as usize];
+                if i < 70 {
+                    bits.push(false);
+                }
+                (i, bits)
+            })
+            .collect();
+        assert!(HuffmanCode::new(chain).satisfies_kraft());
+
+        assert!(lengths_satisfy_kraft([1, 2, 2]));
+        assert!(lengths_satisfy_kraft([0, 1]));
+        assert!(!lengths_satisfy_kraft([1, 1, 2]));
+        assert!(!lengths_satisfy_kraft([2, 2, 2, 2, 3]));
+        assert!(!lengths_satisfy_kraft([100, 100, 100, 1, 1]));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_oversubscribed_lengths() {
+        // Three 1-bit codes cannot coexist in a prefix code.
+        let mut data = 3u32.to_be_bytes().to_vec();
+        data.extend_from_slice(&[b'a', 1, b'b', 1, b'c', 1]);
+        assert_eq!(
+            HuffmanCode::<char>::deserialize(&data).unwrap_err(),
+            FormatError::NotPrefixCode
+        );
+    }
+}
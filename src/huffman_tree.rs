@@ -1,52 +1,51 @@
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap};
-
-#[derive(Debug)]
-struct HuffmanCode {
-    encoding_table: HashMap<char, Vec<bool>>,
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use crate::huffman_encoder::HuffmanCode;
+use crate::symbol::Symbol;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    EmptyFrequencies,
+    MaxLengthTooSmall { max_len: u8, symbols: usize },
 }
 
-impl HuffmanCode {
-    fn new(encoding_table: HashMap<char, Vec<bool>>) -> Self {
-        Self { encoding_table }
-    }
-
-    fn encode(&self, data: &str) -> Vec<bool> {
-        Vec::new()
-    }
-
-    fn decode(&self, data: &[bool]) -> String {
-        String::new()
-    }
-
-    fn serialize(&self) -> Vec<u8> {
-        Vec::new()
-    }
-
-    fn deserialize(data: &[u8]) -> Self {
-        Self { encoding_table: HashMap::new() }
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::EmptyFrequencies => write!(f, "cannot build a tree without symbols"),
+            BuildError::MaxLengthTooSmall { max_len, symbols } => write!(
+                f,
+                "{} symbols cannot be coded with codes of at most {} bits",
+                symbols, max_len
+            ),
+        }
     }
 }
 
+impl Error for BuildError {}
+
 #[derive(Debug)]
-enum Node {
+enum Node<S> {
     Leaf {
-        value: char,
-        count: i32,
+        value: S,
+        count: u64,
     },
     Internal {
-        left: Box<Node>,
-        right: Box<Node>,
-        weight: i32,
+        left: Box<Node<S>>,
+        right: Box<Node<S>>,
+        weight: u64,
     },
 }
 
-impl Node {
+impl<S> Node<S> {
     fn is_leaf(&self) -> bool {
         matches!(self, Node::Leaf { .. })
     }
 
-    fn weight(&self) -> i32 {
+    fn weight(&self) -> u64 {
         match self {
             Node::Leaf { count, .. } => *count,
             Node::Internal { weight, .. } => *weight,
@@ -54,48 +53,110 @@ impl Node {
     }
 }
 
-#[derive(Debug)]
-struct HuffmanTree {
-    root: Box<Node>,
+/// What a `build_tree_with` comparator sees of a subtree waiting to be
+/// merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubtreeSummary<S> {
+    /// Sum of the counts of the subtree's leaves.
+    pub weight: u64,
+    /// Smallest symbol in the subtree.
+    pub min_symbol: S,
+    /// Height of the subtree, 0 for a leaf.
+    pub depth: usize,
+}
+
+impl<S: Symbol> SubtreeSummary<S> {
+    fn merge(left: &Self, right: &Self) -> Self {
+        Self {
+            weight: left.weight + right.weight,
+            min_symbol: left.min_symbol.min(right.min_symbol),
+            depth: left.depth.max(right.depth) + 1,
+        }
+    }
 }
 
-impl HuffmanTree {
+#[derive(Debug)]
+pub struct HuffmanTree<S = char> {
+    root: Box<Node<S>>,
+}
 
-    pub fn build_encoding_table(&self) -> HashMap<char, Vec<bool>> {
+impl<S: Symbol> HuffmanTree<S> {
+    pub fn build_encoding_table(&self) -> HashMap<S, Vec<bool>> {
         let mut encoding_table = HashMap::new();
-        self.walk_through_tree(&self.root, Vec::new(), &mut encoding_table);
+        // A lone leaf would get the empty code, which can't be written to or
+        // read back from a bitstream, so give it a single bit instead.
+        let mut path = if self.root.is_leaf() {
+            vec![false]
+        } else {
+            Vec::new()
+        };
+        self.walk_through_tree(&self.root, &mut path, &mut encoding_table);
         encoding_table
     }
 
+    /// Walks the tree depth first with a single shared path, pushing a bit
+    /// on the way down and popping it on the way back up. Only leaves
+    /// allocate, for the copy of the path that becomes their code.
     fn walk_through_tree(
         &self,
-        node: &Box<Node>,
-        current_path: Vec<bool>,
-        table: &mut HashMap<char, Vec<bool>>,
+        node: &Node<S>,
+        path: &mut Vec<bool>,
+        table: &mut HashMap<S, Vec<bool>>,
     ) {
-        match node.as_ref() {
+        match node {
             Node::Leaf { value, .. } => {
-                table.insert(*value, current_path);
+                table.insert(*value, path.clone());
             }
             Node::Internal { left, right, .. } => {
-                let mut left_path = current_path.clone();
-                left_path.push(false);
-                self.walk_through_tree(left, left_path, table);
+                path.push(false);
+                self.walk_through_tree(left, path, table);
+                path.pop();
+
+                path.push(true);
+                self.walk_through_tree(right, path, table);
+                path.pop();
+            }
+        }
+    }
 
-                let mut right_path = current_path;
-                right_path.push(true); 
-                self.walk_through_tree(right, right_path, table);
+    /// Height of the tree: the number of edges on its longest root-to-leaf
+    /// path. This is the longest code length, except for a lone leaf,
+    /// which has depth 0 but still gets a 1-bit code.
+    pub fn max_depth(&self) -> usize {
+        fn depth<S>(node: &Node<S>) -> usize {
+            match node {
+                Node::Leaf { .. } => 0,
+                Node::Internal { left, right, .. } => 1 + depth(left).max(depth(right)),
             }
         }
+        depth(&self.root)
     }
 
-    fn new_leaf(value: char, count: i32) -> HuffmanTree {
+    /// The symbol and count of every leaf, left to right, so in the order
+    /// of their codes. Walks the tree on demand instead of building a table.
+    pub fn leaves(&self) -> impl Iterator<Item = (S, u64)> + '_ {
+        let mut stack = vec![self.root.as_ref()];
+        std::iter::from_fn(move || {
+            while let Some(node) = stack.pop() {
+                match node {
+                    Node::Leaf { value, count } => return Some((*value, *count)),
+                    Node::Internal { left, right, .. } => {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    fn new_leaf(value: S, count: u64) -> HuffmanTree<S> {
         HuffmanTree {
             root: Box::new(Node::Leaf { value, count }),
         }
     }
 
-    fn new_internal(left: Box<Node>, right: Box<Node>) -> HuffmanTree {
+    fn new_internal(left: Box<Node<S>>, right: Box<Node<S>>) -> HuffmanTree<S> {
         let weight = left.weight() + right.weight();
         HuffmanTree {
             root: Box::new(Node::Internal {
@@ -106,54 +167,357 @@ impl HuffmanTree {
         }
     }
 
-    fn weight(&self) -> i32 {
+    fn weight(&self) -> u64 {
         self.root.weight()
     }
 
-    fn build_tree(frequencies: &HashMap<char, i32>) -> HuffmanTree {
+    /// Whether both trees have the same shape with the same symbol and
+    /// count at every leaf, so that they give the same codes. Unlike `==`,
+    /// which only compares weights, this is what tests and deduplication
+    /// usually want.
+    pub fn structurally_eq(&self, other: &HuffmanTree<S>) -> bool {
+        fn node_eq<S: Symbol>(a: &Node<S>, b: &Node<S>) -> bool {
+            match (a, b) {
+                (
+                    Node::Leaf { value, count },
+                    Node::Leaf {
+                        value: other_value,
+                        count: other_count,
+                    },
+                ) => value == other_value && count == other_count,
+                (
+                    Node::Internal { left, right, .. },
+                    Node::Internal {
+                        left: other_left,
+                        right: other_right,
+                        ..
+                    },
+                ) => node_eq(left, other_left) && node_eq(right, other_right),
+                _ => false,
+            }
+        }
+        node_eq(&self.root, &other.root)
+    }
+
+    pub fn build_tree(frequencies: &HashMap<S, u64>) -> Result<HuffmanTree<S>, BuildError> {
         let mut heap = BinaryHeap::new();
 
         for (&c, &count) in frequencies {
-            heap.push(Reverse(HuffmanTree::new_leaf(c, count)));
+            heap.push(Reverse(MergeCandidate {
+                tree: HuffmanTree::new_leaf(c, count),
+                min_symbol: c,
+            }));
         }
 
         while heap.len() > 1 {
             if let (Some(Reverse(left)), Some(Reverse(right))) = (heap.pop(), heap.pop()) {
-                let combined = HuffmanTree::new_internal(left.root, right.root);
-                heap.push(Reverse(combined));
+                let combined = HuffmanTree::new_internal(left.tree.root, right.tree.root);
+                heap.push(Reverse(MergeCandidate {
+                    tree: combined,
+                    min_symbol: left.min_symbol.min(right.min_symbol),
+                }));
             } else {
                 panic!("Heap should contain at least two elements")
             }
         }
 
-        if let Some(Reverse(tree)) = heap.pop() {
-            tree
-        } else {
-            panic!("Heap should not be empty")
+        match heap.pop() {
+            Some(Reverse(candidate)) => Ok(candidate.tree),
+            None => Err(BuildError::EmptyFrequencies),
+        }
+    }
+
+    /// Builds a tree in linear time from `(symbol, count)` pairs already
+    /// sorted by ascending count, using two queues instead of a heap: one
+    /// holds the leaves in order and the other the merged subtrees, which
+    /// are created in order of weight too. Leaves win ties.
+    ///
+    /// The code lengths are optimal like those of `build_tree`, but the
+    /// tree can differ where counts are equal.
+    pub fn build_tree_sorted(sorted: &[(S, u64)]) -> Result<HuffmanTree<S>, BuildError> {
+        debug_assert!(
+            sorted.windows(2).all(|pair| pair[0].1 <= pair[1].1),
+            "frequencies must be sorted by ascending count"
+        );
+        let mut leaves: VecDeque<Box<Node<S>>> = sorted
+            .iter()
+            .map(|&(c, count)| HuffmanTree::new_leaf(c, count).root)
+            .collect();
+        let mut merged: VecDeque<Box<Node<S>>> = VecDeque::new();
+
+        while leaves.len() + merged.len() > 1 {
+            let left = pop_lightest(&mut leaves, &mut merged).unwrap();
+            let right = pop_lightest(&mut leaves, &mut merged).unwrap();
+            merged.push_back(HuffmanTree::new_internal(left, right).root);
+        }
+
+        match pop_lightest(&mut leaves, &mut merged) {
+            Some(root) => Ok(HuffmanTree { root }),
+            None => Err(BuildError::EmptyFrequencies),
+        }
+    }
+
+    /// Builds a tree like `build_tree`, but merges subtrees in the order
+    /// given by `cmp`: at every step the two smallest subtrees are joined,
+    /// the smallest becoming the left child. `cmp` should order by weight
+    /// first, or the result is not a Huffman tree, and should be a total
+    /// order so the result does not depend on `HashMap` iteration order.
+    ///
+    /// `build_tree` is equivalent to comparing `(weight, min_symbol)`.
+    pub fn build_tree_with<F>(
+        frequencies: &HashMap<S, u64>,
+        mut cmp: F,
+    ) -> Result<HuffmanTree<S>, BuildError>
+    where
+        F: FnMut(&SubtreeSummary<S>, &SubtreeSummary<S>) -> Ordering,
+    {
+        // Kept sorted from largest to smallest so the next two subtrees to
+        // merge are popped off the end.
+        let mut pending: Vec<(SubtreeSummary<S>, Box<Node<S>>)> = Vec::new();
+        for (&c, &count) in frequencies {
+            let summary = SubtreeSummary {
+                weight: count,
+                min_symbol: c,
+                depth: 0,
+            };
+            pending.push((summary, HuffmanTree::new_leaf(c, count).root));
+        }
+        pending.sort_by(|(a, _), (b, _)| cmp(b, a));
+
+        while pending.len() > 1 {
+            let (left_summary, left) = pending.pop().unwrap();
+            let (right_summary, right) = pending.pop().unwrap();
+            let summary = SubtreeSummary::merge(&left_summary, &right_summary);
+            let index =
+                pending.partition_point(|(other, _)| cmp(other, &summary) == Ordering::Greater);
+            pending.insert(
+                index,
+                (summary, HuffmanTree::new_internal(left, right).root),
+            );
+        }
+
+        match pending.pop() {
+            Some((_, root)) => Ok(HuffmanTree { root }),
+            None => Err(BuildError::EmptyFrequencies),
+        }
+    }
+}
+
+impl<S: Symbol> HuffmanTree<S> {
+    /// Builds a canonical code whose codes are at most `max_len` bits long,
+    /// using the package-merge algorithm. The result is optimal among codes
+    /// that respect the limit, and matches plain Huffman code lengths when
+    /// the limit is not binding.
+    pub fn build_length_limited(
+        frequencies: &HashMap<S, u64>,
+        max_len: u8,
+    ) -> Result<HuffmanCode<S>, BuildError> {
+        let symbol_count = frequencies.len();
+        if symbol_count == 0 {
+            return Err(BuildError::EmptyFrequencies);
+        }
+        if max_len == 0 || (max_len < 64 && (1usize << max_len) < symbol_count) {
+            return Err(BuildError::MaxLengthTooSmall {
+                max_len,
+                symbols: symbol_count,
+            });
+        }
+
+        let mut leaves: Vec<(u64, S)> = frequencies.iter().map(|(&c, &count)| (count, c)).collect();
+        leaves.sort();
+
+        let mut lengths: HashMap<S, u8> = leaves.iter().map(|&(_, c)| (c, 0)).collect();
+        if symbol_count == 1 {
+            lengths.insert(leaves[0].1, 1);
+            return Ok(HuffmanCode::canonical(&lengths));
+        }
+
+        // No code can be longer than `symbol_count - 1` bits, so extra
+        // levels beyond that would only repeat the same packages.
+        let level_count = (max_len as usize).min(symbol_count - 1);
+        let mut levels: Vec<Vec<(u64, PackageItem)>> = vec![leaves
+            .iter()
+            .enumerate()
+            .map(|(i, &(weight, _))| (weight, PackageItem::Leaf(i)))
+            .collect()];
+        for _ in 1..level_count {
+            let previous = levels.last().unwrap();
+            let packages = previous
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, pair)| (pair[0].0 + pair[1].0, PackageItem::Package(2 * i)));
+
+            // Merge leaves and packages by weight; leaves win ties.
+            let mut merged = Vec::with_capacity(symbol_count + previous.len() / 2);
+            let mut leaf_iter = leaves
+                .iter()
+                .enumerate()
+                .map(|(i, &(weight, _))| (weight, PackageItem::Leaf(i)))
+                .peekable();
+            let mut package_iter = packages.peekable();
+            loop {
+                let take_leaf = match (leaf_iter.peek(), package_iter.peek()) {
+                    (Some(leaf), Some(package)) => leaf.0 <= package.0,
+                    (Some(_), None) => true,
+                    (None, Some(_)) => false,
+                    (None, None) => break,
+                };
+                let item = if take_leaf {
+                    leaf_iter.next()
+                } else {
+                    package_iter.next()
+                };
+                merged.push(item.unwrap());
+            }
+            levels.push(merged);
+        }
+
+        // Every leaf inside the 2n - 2 lightest items of the last level adds
+        // one bit to the code length of its symbol.
+        let mut leaf_depths = vec![0u8; symbol_count];
+        let last_level = levels.len() - 1;
+        for index in 0..2 * symbol_count - 2 {
+            count_leaves(&levels, last_level, index, &mut leaf_depths);
+        }
+        for (i, &(_, c)) in leaves.iter().enumerate() {
+            lengths.insert(c, leaf_depths[i]);
+        }
+        Ok(HuffmanCode::canonical(&lengths))
+    }
+}
+
+/// Pops the lighter of the two queue fronts for `build_tree_sorted`,
+/// preferring the leaf when the weights are equal.
+fn pop_lightest<S>(
+    leaves: &mut VecDeque<Box<Node<S>>>,
+    merged: &mut VecDeque<Box<Node<S>>>,
+) -> Option<Box<Node<S>>> {
+    match (leaves.front(), merged.front()) {
+        (Some(leaf), Some(subtree)) if subtree.weight() < leaf.weight() => merged.pop_front(),
+        (Some(_), _) => leaves.pop_front(),
+        (None, _) => merged.pop_front(),
+    }
+}
+
+/// Item of a package-merge level: either a leaf (index into the sorted
+/// leaves) or the package of items `i` and `i + 1` of the previous level.
+#[derive(Clone, Copy)]
+enum PackageItem {
+    Leaf(usize),
+    Package(usize),
+}
+
+fn count_leaves(
+    levels: &[Vec<(u64, PackageItem)>],
+    level: usize,
+    index: usize,
+    leaf_depths: &mut [u8],
+) {
+    match levels[level][index].1 {
+        PackageItem::Leaf(leaf) => leaf_depths[leaf] += 1,
+        PackageItem::Package(first) => {
+            count_leaves(levels, level - 1, first, leaf_depths);
+            count_leaves(levels, level - 1, first + 1, leaf_depths);
+        }
+    }
+}
+
+/// Renders the tree one node per line, children indented below their
+/// parent and prefixed with the bit of their edge. Internal nodes show
+/// their weight in brackets, leaves their symbol and count:
+///
+/// ```text
+/// [3]
+///   0: 'b' 1
+///   1: 'a' 2
+/// ```
+impl<S: Symbol> fmt::Display for HuffmanTree<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(f, &self.root, 0, None)
+    }
+}
+
+fn write_node<S: Symbol>(
+    f: &mut fmt::Formatter<'_>,
+    node: &Node<S>,
+    depth: usize,
+    edge: Option<bool>,
+) -> fmt::Result {
+    write!(f, "{:indent$}", "", indent = depth * 2)?;
+    if let Some(bit) = edge {
+        write!(f, "{}: ", bit as u8)?;
+    }
+    match node {
+        Node::Leaf { value, count } => writeln!(f, "{:?} {}", value, count),
+        Node::Internal {
+            left,
+            right,
+            weight,
+        } => {
+            writeln!(f, "[{}]", weight)?;
+            write_node(f, left, depth + 1, Some(false))?;
+            write_node(f, right, depth + 1, Some(true))
         }
     }
 }
 
-impl PartialEq for HuffmanTree {
+/// Trees compare by weight alone, which is the order merging needs. Use
+/// `structurally_eq` to compare their contents.
+impl<S: Symbol> PartialEq for HuffmanTree<S> {
     fn eq(&self, other: &Self) -> bool {
         self.weight() == other.weight()
     }
 }
 
-impl Eq for HuffmanTree {}
+impl<S: Symbol> Eq for HuffmanTree<S> {}
 
-impl PartialOrd for HuffmanTree {
+impl<S: Symbol> PartialOrd for HuffmanTree<S> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for HuffmanTree {
+impl<S: Symbol> Ord for HuffmanTree<S> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.weight().cmp(&other.weight())
     }
 }
 
+/// Heap entry used by `build_tree`. Subtrees are ordered by weight, and ties
+/// are broken by the smallest symbol they contain so that the same
+/// frequencies always produce the same tree, whatever the `HashMap`
+/// iteration order.
+struct MergeCandidate<S> {
+    tree: HuffmanTree<S>,
+    min_symbol: S,
+}
+
+impl<S: Symbol> MergeCandidate<S> {
+    fn key(&self) -> (u64, S) {
+        (self.tree.weight(), self.min_symbol)
+    }
+}
+
+impl<S: Symbol> PartialEq for MergeCandidate<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<S: Symbol> Eq for MergeCandidate<S> {}
+
+impl<S: Symbol> PartialOrd for MergeCandidate<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: Symbol> Ord for MergeCandidate<S> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,6 +547,57 @@ mod tests {
         assert_eq!(tree1, HuffmanTree::new_leaf('x', 3)); // Same weight, different char
     }
 
+    #[test]
+    fn test_leaves_yields_every_leaf_left_to_right() {
+        // ((a:1, b:2), c:4)
+        let a = HuffmanTree::new_leaf('a', 1).root;
+        let b = HuffmanTree::new_leaf('b', 2).root;
+        let ab = HuffmanTree::new_internal(a, b).root;
+        let c = HuffmanTree::new_leaf('c', 4).root;
+        let tree = HuffmanTree::new_internal(ab, c);
+        assert_eq!(
+            tree.leaves().collect::<Vec<_>>(),
+            [('a', 1), ('b', 2), ('c', 4)]
+        );
+
+        assert_eq!(
+            HuffmanTree::new_leaf(7u8, 3).leaves().collect::<Vec<_>>(),
+            [(7, 3)]
+        );
+
+        let frequencies = HashMap::from([('x', 5), ('y', 1), ('z', 2), ('w', 9)]);
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+        let leaves: HashMap<char, u64> = tree.leaves().collect();
+        assert_eq!(leaves, frequencies);
+    }
+
+    #[test]
+    fn test_structurally_eq_compares_shape_and_leaves() {
+        let tree = |right_heavy: bool| {
+            let a = HuffmanTree::new_leaf('a', 2).root;
+            let b = HuffmanTree::new_leaf('b', 2).root;
+            let c = HuffmanTree::new_leaf('c', 2).root;
+            let bc = HuffmanTree::new_internal(b, c).root;
+            if right_heavy {
+                HuffmanTree::new_internal(a, bc)
+            } else {
+                HuffmanTree::new_internal(bc, a)
+            }
+        };
+
+        assert_eq!(tree(true), tree(false));
+        assert!(!tree(true).structurally_eq(&tree(false)));
+        assert!(tree(true).structurally_eq(&tree(true)));
+
+        let a = HuffmanTree::new_leaf('a', 2);
+        assert!(!a.structurally_eq(&HuffmanTree::new_leaf('x', 2)));
+        assert!(!a.structurally_eq(&HuffmanTree::new_leaf('a', 3)));
+
+        let frequencies = HashMap::from([('a', 5), ('b', 2), ('c', 1), ('d', 1)]);
+        let built = HuffmanTree::build_tree(&frequencies).unwrap();
+        assert!(built.structurally_eq(&HuffmanTree::build_tree(&frequencies).unwrap()));
+    }
+
     #[test]
     fn test_deep_tree() {
         let leaf1 = HuffmanTree::new_leaf('a', 1);
@@ -203,7 +618,7 @@ mod tests {
         frequencies.insert('c', 1);
         frequencies.insert('d', 5);
 
-        let tree = HuffmanTree::build_tree(&frequencies);
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
         assert_eq!(tree.weight(), 12);
 
         match tree.root.as_ref() {
@@ -236,12 +651,12 @@ mod tests {
         frequencies.insert('c', 1);
         frequencies.insert('d', 5);
 
-        let tree = HuffmanTree::build_tree(&frequencies);
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
         let encoding_table = tree.build_encoding_table();
 
         assert_eq!(encoding_table.len(), 4);
         assert!(encoding_table.contains_key(&'a'));
-        assert!(encoding_table.contains_key(&'b')); 
+        assert!(encoding_table.contains_key(&'b'));
         assert!(encoding_table.contains_key(&'c'));
         assert!(encoding_table.contains_key(&'d'));
 
@@ -250,4 +665,352 @@ mod tests {
         assert_eq!(encoding_table[&'b'], vec![true, false, true]);
         assert_eq!(encoding_table[&'c'], vec![true, false, false]);
     }
+
+    /// The table as built by cloning the path at every internal node,
+    /// which `build_encoding_table` used to do.
+    fn cloning_walk(node: &Node<char>, path: Vec<bool>, table: &mut HashMap<char, Vec<bool>>) {
+        match node {
+            Node::Leaf { value, .. } => {
+                table.insert(*value, path);
+            }
+            Node::Internal { left, right, .. } => {
+                let mut left_path = path.clone();
+                left_path.push(false);
+                cloning_walk(left, left_path, table);
+                let mut right_path = path;
+                right_path.push(true);
+                cloning_walk(right, right_path, table);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_path_walk_matches_cloning_walk() {
+        let alphabet: HashMap<char, u64> = ('\u{20}'..'\u{220}')
+            .enumerate()
+            .map(|(i, c)| (c, (i as u64 * 7919) % 1000 + 1))
+            .collect();
+        for frequencies in [fibonacci_frequencies(), alphabet] {
+            let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+            let mut expected = HashMap::new();
+            cloning_walk(&tree.root, Vec::new(), &mut expected);
+            assert_eq!(tree.build_encoding_table(), expected);
+        }
+    }
+
+    #[test]
+    fn test_counts_beyond_i32_do_not_overflow() {
+        let big = 3_000_000_000u64;
+        let frequencies = HashMap::from([('a', big), ('b', 2 * big), ('c', 1), ('d', 4 * big)]);
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+        assert_eq!(tree.weight(), 7 * big + 1);
+
+        let small = HuffmanTree::new_leaf('x', big);
+        let large = HuffmanTree::new_leaf('y', 2 * big);
+        assert!(small < large);
+
+        let table = tree.build_encoding_table();
+        assert_eq!(table[&'d'].len(), 1);
+        assert_eq!(table[&'b'].len(), 2);
+        assert_eq!(table[&'a'].len(), 3);
+        assert_eq!(table[&'c'].len(), 3);
+    }
+
+    #[test]
+    fn test_display_renders_structure() {
+        let frequencies = HashMap::from([('a', 4), ('b', 2), ('c', 1), ('d', 5)]);
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+
+        let expected = "\
+[12]
+  0: 'd' 5
+  1: [7]
+    0: [3]
+      0: 'c' 1
+      1: 'b' 2
+    1: 'a' 4
+";
+        assert_eq!(tree.to_string(), expected);
+        assert_eq!(HuffmanTree::new_leaf('x', 3).to_string(), "'x' 3\n");
+    }
+
+    #[test]
+    fn test_build_tree_breaks_ties_by_symbol() {
+        let mut frequencies = HashMap::new();
+        for c in ['d', 'b', 'c', 'a'] {
+            frequencies.insert(c, 1);
+        }
+
+        let encoding_table = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(encoding_table[&'a'], vec![false, false]);
+        assert_eq!(encoding_table[&'b'], vec![false, true]);
+        assert_eq!(encoding_table[&'c'], vec![true, false]);
+        assert_eq!(encoding_table[&'d'], vec![true, true]);
+    }
+
+    #[test]
+    fn test_build_tree_is_deterministic() {
+        let symbols: Vec<char> = ('a'..='z').collect();
+        let mut forward = HashMap::new();
+        for (i, &c) in symbols.iter().enumerate() {
+            forward.insert(c, (i % 4) as u64 + 1);
+        }
+        // Same contents, different insertion order and capacity, so the
+        // iteration order differs from `forward`.
+        let mut backward = HashMap::with_capacity(1024);
+        for c in symbols.iter().rev() {
+            backward.insert(*c, forward[c]);
+        }
+
+        let first = HuffmanTree::build_tree(&forward)
+            .unwrap()
+            .build_encoding_table();
+        let second = HuffmanTree::build_tree(&backward)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_single_symbol_encoding_table() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 4);
+
+        let encoding_table = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(encoding_table.len(), 1);
+        assert_eq!(encoding_table[&'a'], vec![false]);
+    }
+
+    #[test]
+    fn test_build_tree_empty_frequencies() {
+        let frequencies: HashMap<char, u64> = HashMap::new();
+        assert_eq!(
+            HuffmanTree::build_tree(&frequencies).unwrap_err(),
+            BuildError::EmptyFrequencies
+        );
+    }
+
+    #[test]
+    fn test_build_tree_over_bytes() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert(0x00u8, 4);
+        frequencies.insert(0xFF, 2);
+        frequencies.insert(b'a', 1);
+        frequencies.insert(0x7F, 5);
+
+        let encoding_table = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(encoding_table[&0x7F], vec![false]);
+        assert_eq!(encoding_table[&0x00], vec![true, true]);
+        assert_eq!(encoding_table[&0xFF], vec![true, false, true]);
+        assert_eq!(encoding_table[&b'a'], vec![true, false, false]);
+    }
+
+    fn fibonacci_frequencies() -> HashMap<char, u64> {
+        let mut frequencies = HashMap::new();
+        let (mut a, mut b) = (1, 1);
+        for c in 'a'..='j' {
+            frequencies.insert(c, a);
+            (a, b) = (b, a + b);
+        }
+        frequencies
+    }
+
+    #[test]
+    fn test_max_depth_of_skewed_tree() {
+        // Fibonacci weights merge one symbol at a time, giving a chain as
+        // deep as the tree can get: one level per symbol but the last.
+        let frequencies = fibonacci_frequencies();
+        let tree = HuffmanTree::build_tree(&frequencies).unwrap();
+        assert_eq!(tree.max_depth(), frequencies.len() - 1);
+
+        let code = HuffmanCode::new(tree.build_encoding_table());
+        assert_eq!(code.max_code_len(), tree.max_depth());
+
+        let lone = HuffmanTree::build_tree(&HashMap::from([('x', 3)])).unwrap();
+        assert_eq!(lone.max_depth(), 0);
+        assert_eq!(
+            HuffmanCode::new(lone.build_encoding_table()).max_code_len(),
+            1
+        );
+    }
+
+    fn lengths_of(tree: &HuffmanTree) -> HashMap<char, u8> {
+        HuffmanCode::new(tree.build_encoding_table()).code_lengths()
+    }
+
+    #[test]
+    fn test_build_tree_with_default_order_matches_build_tree() {
+        for frequencies in [
+            fibonacci_frequencies(),
+            HashMap::from([('a', 2), ('b', 2), ('c', 2), ('d', 2)]),
+        ] {
+            let tree = HuffmanTree::build_tree_with(&frequencies, |a, b| {
+                (a.weight, a.min_symbol).cmp(&(b.weight, b.min_symbol))
+            })
+            .unwrap();
+            assert_eq!(
+                tree.build_encoding_table(),
+                HuffmanTree::build_tree(&frequencies)
+                    .unwrap()
+                    .build_encoding_table()
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_tree_with_tie_breaking_by_depth() {
+        // After merging a and b there are three subtrees of weight 2, so the
+        // tie-breaking decides the shape.
+        let frequencies = HashMap::from([('a', 1), ('b', 1), ('c', 2), ('d', 2)]);
+        let shallow = HuffmanTree::build_tree_with(&frequencies, |x, y| {
+            (x.weight, x.depth, x.min_symbol).cmp(&(y.weight, y.depth, y.min_symbol))
+        })
+        .unwrap();
+        let deep = HuffmanTree::build_tree_with(&frequencies, |x, y| {
+            (x.weight, Reverse(x.depth), x.min_symbol).cmp(&(
+                y.weight,
+                Reverse(y.depth),
+                y.min_symbol,
+            ))
+        })
+        .unwrap();
+
+        let shallow_lengths = lengths_of(&shallow);
+        let deep_lengths = lengths_of(&deep);
+        assert_eq!(shallow.max_depth(), 2);
+        assert_eq!(deep.max_depth(), 3);
+        assert_ne!(shallow_lengths, deep_lengths);
+
+        // Both are Huffman trees: same weight and same optimal cost.
+        assert_eq!(shallow.weight(), 6);
+        assert_eq!(deep.weight(), 6);
+        assert_eq!(
+            total_cost(&frequencies, &shallow_lengths),
+            total_cost(&frequencies, &deep_lengths)
+        );
+        assert!(HuffmanCode::try_new(deep.build_encoding_table()).is_ok());
+    }
+
+    #[test]
+    fn test_build_tree_sorted_matches_heap_lengths() {
+        let alphabet: HashMap<char, u64> = ('a'..='z')
+            .enumerate()
+            .map(|(i, c)| (c, (i as u64 * 37) % 11 + 1))
+            .collect();
+        for frequencies in [fibonacci_frequencies(), alphabet] {
+            let mut sorted: Vec<(char, u64)> = frequencies.iter().map(|(&c, &n)| (c, n)).collect();
+            sorted.sort_by_key(|&(c, count)| (count, c));
+
+            let linear = HuffmanTree::build_tree_sorted(&sorted).unwrap();
+            let heap = HuffmanTree::build_tree(&frequencies).unwrap();
+            assert_eq!(linear.weight(), heap.weight());
+            let linear_lengths = lengths_of(&linear);
+            assert_eq!(
+                total_cost(&frequencies, &linear_lengths),
+                total_cost(&frequencies, &lengths_of(&heap))
+            );
+            // Without ties the two methods must agree exactly.
+            if frequencies == fibonacci_frequencies() {
+                assert_eq!(linear_lengths, lengths_of(&heap));
+            }
+        }
+
+        let lone = HuffmanTree::build_tree_sorted(&[('x', 3)]).unwrap();
+        assert_eq!(lone.build_encoding_table()[&'x'], vec![false]);
+        assert_eq!(
+            HuffmanTree::<char>::build_tree_sorted(&[]).unwrap_err(),
+            BuildError::EmptyFrequencies
+        );
+    }
+
+    #[test]
+    fn test_build_tree_with_empty_frequencies() {
+        let result =
+            HuffmanTree::<char>::build_tree_with(&HashMap::new(), |a, b| a.weight.cmp(&b.weight));
+        assert_eq!(result.unwrap_err(), BuildError::EmptyFrequencies);
+    }
+
+    fn total_cost(frequencies: &HashMap<char, u64>, lengths: &HashMap<char, u8>) -> u64 {
+        frequencies
+            .iter()
+            .map(|(c, &count)| count * lengths[c] as u64)
+            .sum()
+    }
+
+    #[test]
+    fn test_length_limited_respects_max_len() {
+        let frequencies = fibonacci_frequencies();
+        let unlimited = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table();
+        assert_eq!(unlimited.values().map(Vec::len).max(), Some(9));
+
+        let code = HuffmanTree::build_length_limited(&frequencies, 4).unwrap();
+        let lengths = code.code_lengths();
+        assert_eq!(lengths.len(), frequencies.len());
+        assert!(lengths.values().all(|&len| (1..=4).contains(&len)));
+        let kraft: f64 = lengths.values().map(|&len| 0.5f64.powi(len as i32)).sum();
+        assert!(kraft <= 1.0);
+
+        let bits = code.encode("abcdefghij").unwrap();
+        assert_eq!(code.decode(&bits).unwrap(), "abcdefghij");
+    }
+
+    #[test]
+    fn test_length_limited_relaxed_matches_huffman() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 4);
+        frequencies.insert('b', 2);
+        frequencies.insert('c', 1);
+        frequencies.insert('d', 5);
+
+        let huffman_lengths: HashMap<char, u8> = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table()
+            .into_iter()
+            .map(|(c, code)| (c, code.len() as u8))
+            .collect();
+        let limited = HuffmanTree::build_length_limited(&frequencies, 16).unwrap();
+        assert_eq!(limited.code_lengths(), huffman_lengths);
+
+        let frequencies = fibonacci_frequencies();
+        let huffman_lengths: HashMap<char, u8> = HuffmanTree::build_tree(&frequencies)
+            .unwrap()
+            .build_encoding_table()
+            .into_iter()
+            .map(|(c, code)| (c, code.len() as u8))
+            .collect();
+        let limited = HuffmanTree::build_length_limited(&frequencies, 9).unwrap();
+        assert_eq!(
+            total_cost(&frequencies, &limited.code_lengths()),
+            total_cost(&frequencies, &huffman_lengths)
+        );
+    }
+
+    #[test]
+    fn test_length_limited_rejects_too_small_limit() {
+        let frequencies = fibonacci_frequencies();
+        assert_eq!(
+            HuffmanTree::build_length_limited(&frequencies, 3).unwrap_err(),
+            BuildError::MaxLengthTooSmall {
+                max_len: 3,
+                symbols: 10
+            }
+        );
+        assert!(HuffmanTree::build_length_limited(&frequencies, 4).is_ok());
+    }
+
+    #[test]
+    fn test_length_limited_single_symbol() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 3);
+        let code = HuffmanTree::build_length_limited(&frequencies, 1).unwrap();
+        assert_eq!(code.code_lengths()[&'a'], 1);
+    }
 }
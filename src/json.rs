@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write as _;
+
+use crate::huffman_encoder::{FormatError, HuffmanCode};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum JsonError {
+    /// The input ended inside the object.
+    UnexpectedEnd,
+    /// Found `found` at byte `offset`, where something else was expected.
+    UnexpectedChar {
+        found: char,
+        offset: usize,
+    },
+    InvalidEscape {
+        offset: usize,
+    },
+    /// A key is not exactly one char.
+    InvalidSymbol(String),
+    /// A value contains something other than `0` and `1`.
+    InvalidBits(String),
+    /// The table parsed but is not a usable code.
+    InvalidCode(FormatError),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedEnd => write!(f, "JSON ends unexpectedly"),
+            JsonError::UnexpectedChar { found, offset } => {
+                write!(f, "unexpected {:?} at byte {}", found, offset)
+            }
+            JsonError::InvalidEscape { offset } => {
+                write!(f, "invalid escape sequence at byte {}", offset)
+            }
+            JsonError::InvalidSymbol(key) => write!(f, "key {:?} is not a single char", key),
+            JsonError::InvalidBits(value) => write!(f, "code {:?} is not a bit string", value),
+            JsonError::InvalidCode(e) => write!(f, "invalid code table: {}", e),
+        }
+    }
+}
+
+impl Error for JsonError {}
+
+impl HuffmanCode<char> {
+    /// Serializes the table as a JSON object mapping each char to its code
+    /// as a string of `0`s and `1`s, e.g. `{"a": "0", "b": "10"}`. Entries
+    /// are ordered like `codes_sorted`, so equal codes give equal JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        for (i, (c, code)) in self.codes_sorted().into_iter().enumerate() {
+            if i > 0 {
+                json.push_str(", ");
+            }
+            write_json_string(&mut json, &c.to_string());
+            json.push_str(": \"");
+            json.extend(code.iter().map(|&bit| if bit { '1' } else { '0' }));
+            json.push('"');
+        }
+        json.push('}');
+        json
+    }
+
+    /// Parses a table written by `to_json`. Any JSON object with
+    /// single-char keys and bit-string values is accepted, as long as the
+    /// codes form a prefix code.
+    pub fn from_json(json: &str) -> Result<Self, JsonError> {
+        let mut parser = Parser { json, pos: 0 };
+        let mut encoding_table = HashMap::new();
+
+        parser.expect('{')?;
+        if !parser.consume_if('}') {
+            loop {
+                let key = parser.parse_string()?;
+                let mut chars = key.chars();
+                let c = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(JsonError::InvalidSymbol(key)),
+                };
+                parser.expect(':')?;
+                let value = parser.parse_string()?;
+                let code = value
+                    .chars()
+                    .map(|bit| match bit {
+                        '0' => Ok(false),
+                        '1' => Ok(true),
+                        _ => Err(JsonError::InvalidBits(value.clone())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                encoding_table.insert(c, code);
+
+                if parser.consume_if('}') {
+                    break;
+                }
+                parser.expect(',')?;
+            }
+        }
+        parser.skip_whitespace();
+        if let Some(found) = parser.peek() {
+            return Err(JsonError::UnexpectedChar {
+                found,
+                offset: parser.pos,
+            });
+        }
+
+        HuffmanCode::try_new(encoding_table).map_err(JsonError::InvalidCode)
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(out, "\\u{:04x}", unit).unwrap();
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Just enough of a JSON parser for a flat object of strings.
+struct Parser<'a> {
+    json: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.json[self.pos..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Result<char, JsonError> {
+        let c = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self
+            .peek()
+            .filter(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn consume_if(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        let offset = self.pos;
+        match self.next_char()? {
+            c if c == expected => Ok(()),
+            found => Err(JsonError::UnexpectedChar { found, offset }),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let offset = self.pos;
+            match self.next_char()? {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.next_char()? {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.parse_unicode_escape(offset)?,
+                        _ => return Err(JsonError::InvalidEscape { offset }),
+                    };
+                    s.push(c);
+                }
+                c if c.is_control() => return Err(JsonError::UnexpectedChar { found: c, offset }),
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Parses the digits of a `\u` escape, combining surrogate pairs.
+    fn parse_unicode_escape(&mut self, offset: usize) -> Result<char, JsonError> {
+        let high = self.parse_hex4(offset)?;
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if self.next_char()? != '\\' || self.next_char()? != 'u' {
+                return Err(JsonError::InvalidEscape { offset });
+            }
+            let low = self.parse_hex4(offset)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(JsonError::InvalidEscape { offset });
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code_point).ok_or(JsonError::InvalidEscape { offset })
+    }
+
+    fn parse_hex4(&mut self, offset: usize) -> Result<u32, JsonError> {
+        let digits = self
+            .json
+            .get(self.pos..self.pos + 4)
+            .ok_or(JsonError::UnexpectedEnd)?;
+        let value =
+            u32::from_str_radix(digits, 16).map_err(|_| JsonError::InvalidEscape { offset })?;
+        self.pos += 4;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_chars;
+
+    #[test]
+    fn test_to_json_known_table() {
+        let table = HashMap::from([
+            ('a', vec![false]),
+            ('b', vec![true, false]),
+            ('c', vec![true, true]),
+        ]);
+        let code = HuffmanCode::new(table);
+        assert_eq!(code.to_json(), r#"{"a": "0", "b": "10", "c": "11"}"#);
+    }
+
+    #[test]
+    fn test_json_round_trip_with_escapes() {
+        let text = "quote \" backslash \\ tab\t newline\n bell\u{7} ünï 🎉";
+        let code = HuffmanCode::from_frequencies(&count_chars(text)).unwrap();
+
+        let json = code.to_json();
+        let restored = HuffmanCode::from_json(&json).unwrap();
+        assert_eq!(restored.codes_sorted(), code.codes_sorted());
+        assert_eq!(restored.to_json(), json);
+    }
+
+    #[test]
+    fn test_from_json_accepts_whitespace_and_unicode_escapes() {
+        let json = "{\n  \"\\u00e9\" : \"0\",\n  \"\\ud83c\\udf89\": \"1\"\n}\n";
+        let code = HuffmanCode::from_json(json).unwrap();
+        assert_eq!(
+            code.codes_sorted(),
+            vec![('é', vec![false]), ('🎉', vec![true])]
+        );
+    }
+
+    #[test]
+    fn test_from_json_rejects_malformed_tables() {
+        assert_eq!(
+            HuffmanCode::from_json(r#"{"ab": "0"}"#).unwrap_err(),
+            JsonError::InvalidSymbol("ab".to_string())
+        );
+        assert_eq!(
+            HuffmanCode::from_json(r#"{"a": "012"}"#).unwrap_err(),
+            JsonError::InvalidBits("012".to_string())
+        );
+        assert_eq!(
+            HuffmanCode::from_json(r#"{"a": "1", "b": "10"}"#).unwrap_err(),
+            JsonError::InvalidCode(FormatError::NotPrefixCode)
+        );
+        assert_eq!(
+            HuffmanCode::from_json(r#"{"a": "0""#).unwrap_err(),
+            JsonError::UnexpectedEnd
+        );
+        assert_eq!(
+            HuffmanCode::from_json(r#"{"a": "0"} x"#).unwrap_err(),
+            JsonError::UnexpectedChar {
+                found: 'x',
+                offset: 11
+            }
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Huffman coding for text and raw bytes.
+//!
+//! Count symbol frequencies, build a code from them, then encode and decode
+//! bitstreams or whole containers:
+//!
+//! ```
+//! use huffman_challenge::HuffmanCode;
+//!
+//! let text = "hello huffman world";
+//! let code = HuffmanCode::from_reader(text.as_bytes()).unwrap();
+//!
+//! let bits = code.encode(text).unwrap();
+//! assert!(bits.len() < text.len() * 8);
+//! assert_eq!(code.decode(&bits).unwrap(), text);
+//! ```
+
+pub mod adaptive;
+pub mod archive;
+pub mod bits;
+pub mod blocks;
+pub mod builder;
+pub mod checksum;
+pub mod counter;
+pub mod decode_trie;
+pub mod error;
+pub mod escape;
+pub mod huffman_encoder;
+pub mod huffman_tree;
+pub mod json;
+pub mod lines;
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+mod mmap;
+pub mod model;
+pub mod records;
+pub mod sealed;
+pub mod stats;
+pub mod symbol;
+pub mod transform;
+
+pub use adaptive::AdaptiveHuffman;
+pub use archive::{read_archive, write_archive, ArchiveEntry, ArchiveError};
+pub use bits::BitOrder;
+pub use blocks::{read_blocks, write_blocks, BlocksError};
+pub use builder::HuffmanCodeBuilder;
+pub use counter::{count_symbols, create_counter, merge_counters};
+pub use decode_trie::{DecodeTrie, Step};
+pub use error::HuffmanError;
+pub use escape::EscapeCode;
+pub use huffman_encoder::{
+    compress_symbols, read_container, write_adaptive_container, write_compressed_container,
+    write_compressed_container_with_options, write_container, write_stored_container,
+    write_transformed_container, BlockType, Container, DecodeError, DecodeOptions, EncodeError,
+    EncodeOptions, Encoder, FormatError, HuffmanCode, TableFormat,
+};
+pub use huffman_tree::{BuildError, HuffmanTree, SubtreeSummary};
+pub use json::JsonError;
+pub use lines::{read_lines, write_lines, LineFile, LinesError};
+pub use records::{read_records, write_records, RecordsError};
+pub use sealed::SealedCode;
+pub use stats::CompressionStats;
+pub use symbol::{Symbol, Symbols};
+pub use transform::Transform;
@@ -0,0 +1,241 @@
+//! Text compressed line by line with one shared code, so that any line can
+//! be decoded without touching the others.
+//!
+//! This suits append-only logs: the code is stored once in the header and
+//! every line gets its own length-prefixed block, so reading line `i` only
+//! has to skip over the blocks before it.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::bits::pack_bits;
+use crate::checksum::crc32;
+use crate::huffman_encoder::{ByteReader, DecodeError, EncodeError, FormatError, HuffmanCode};
+use crate::huffman_tree::HuffmanTree;
+
+const LINES_MAGIC: &[u8; 4] = b"HUFL";
+const LINES_VERSION: u8 = 1;
+/// The shared code is stored as canonical lengths, which need codes of at
+/// most 64 bits.
+const MAX_CODE_LEN: u8 = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinesError {
+    /// The file or its table is malformed.
+    Format(FormatError),
+    /// A line's block does not decode or fails its checksum.
+    Decode(DecodeError),
+    /// The text has a char the frequencies do not cover.
+    Encode(EncodeError),
+    LineOutOfRange {
+        index: usize,
+        lines: usize,
+    },
+}
+
+impl fmt::Display for LinesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinesError::Format(e) => write!(f, "invalid line file: {}", e),
+            LinesError::Decode(e) => write!(f, "corrupt line: {}", e),
+            LinesError::Encode(e) => write!(f, "cannot encode line: {}", e),
+            LinesError::LineOutOfRange { index, lines } => {
+                write!(f, "line {} is out of range, the file has {}", index, lines)
+            }
+        }
+    }
+}
+
+impl Error for LinesError {}
+
+impl From<FormatError> for LinesError {
+    fn from(e: FormatError) -> Self {
+        LinesError::Format(e)
+    }
+}
+
+impl From<DecodeError> for LinesError {
+    fn from(e: DecodeError) -> Self {
+        LinesError::Decode(e)
+    }
+}
+
+impl From<EncodeError> for LinesError {
+    fn from(e: EncodeError) -> Self {
+        LinesError::Encode(e)
+    }
+}
+
+/// Compresses every line of `text` on its own, with one code built from
+/// `frequencies` and limited to 64-bit codes. Lines keep their `\n`, so
+/// decoding them all gives back `text` exactly.
+///
+/// Layout, all integers big-endian:
+///
+/// ```text
+/// magic        4 bytes   b"HUFL"
+/// version      1 byte    LINES_VERSION
+/// table        code lengths, as `HuffmanCode::serialize` writes them
+/// line count   8 bytes
+/// lines        per line: CRC-32 of the line (4 bytes), bit length
+///              (8 bytes), packed bits zero-padded to a whole byte
+/// ```
+pub fn write_lines(frequencies: &HashMap<char, u64>, text: &str) -> Result<Vec<u8>, LinesError> {
+    let code = if frequencies.is_empty() {
+        HuffmanCode::new(HashMap::new())
+    } else {
+        HuffmanTree::build_length_limited(frequencies, MAX_CODE_LEN)
+            .expect("a non-empty alphabet fits in 64-bit codes")
+    };
+
+    let mut out = Vec::new();
+    out.extend_from_slice(LINES_MAGIC);
+    out.push(LINES_VERSION);
+    out.extend_from_slice(&code.serialize());
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    out.extend_from_slice(&(lines.len() as u64).to_be_bytes());
+    for line in lines {
+        let bits = code.encode(line)?;
+        out.extend_from_slice(&crc32(line.as_bytes()).to_be_bytes());
+        out.extend_from_slice(&(bits.len() as u64).to_be_bytes());
+        out.extend_from_slice(&pack_bits(&bits));
+    }
+    Ok(out)
+}
+
+/// Whether `data` starts like a file written by `write_lines`.
+pub fn is_line_file(data: &[u8]) -> bool {
+    data.starts_with(LINES_MAGIC)
+}
+
+/// A file written by `write_lines`, with the position of every line's block
+/// known but nothing decoded yet.
+#[derive(Debug)]
+pub struct LineFile<'a> {
+    code: HuffmanCode<char>,
+    lines: Vec<LineBlock<'a>>,
+}
+
+#[derive(Debug)]
+struct LineBlock<'a> {
+    checksum: u32,
+    bit_len: usize,
+    payload: &'a [u8],
+}
+
+impl LineFile<'_> {
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Decodes line `index`, counting from 0, and checks it against its
+    /// checksum. The line keeps its `\n`, if it had one.
+    pub fn line(&self, index: usize) -> Result<String, LinesError> {
+        let block = self.lines.get(index).ok_or(LinesError::LineOutOfRange {
+            index,
+            lines: self.lines.len(),
+        })?;
+        let line = self.code.decode_bytes(block.payload, block.bit_len)?;
+        let actual = crc32(line.as_bytes());
+        if actual != block.checksum {
+            return Err(DecodeError::ChecksumMismatch {
+                expected: block.checksum,
+                actual,
+            }
+            .into());
+        }
+        Ok(line)
+    }
+
+    /// Decodes every line, giving back the text passed to `write_lines`.
+    pub fn decode(&self) -> Result<String, LinesError> {
+        (0..self.len()).map(|index| self.line(index)).collect()
+    }
+}
+
+/// Reads the header of a file written by `write_lines` and locates every
+/// line's block, skipping over the payloads.
+pub fn read_lines(data: &[u8]) -> Result<LineFile<'_>, LinesError> {
+    let mut reader = ByteReader::new(data);
+    if reader.take(LINES_MAGIC.len())? != LINES_MAGIC {
+        return Err(FormatError::BadMagic.into());
+    }
+    let version = reader.read_u8()?;
+    if version != LINES_VERSION {
+        return Err(FormatError::UnsupportedVersion(version).into());
+    }
+    let code = HuffmanCode::read_canonical(&mut reader)?;
+
+    let line_count = reader.read_u64()?;
+    let mut lines = Vec::new();
+    for _ in 0..line_count {
+        let checksum = reader.read_u32()?;
+        let bit_len = usize::try_from(reader.read_u64()?).map_err(|_| FormatError::Truncated)?;
+        let payload = reader.take(bit_len.div_ceil(8))?;
+        lines.push(LineBlock {
+            checksum,
+            bit_len,
+            payload,
+        });
+    }
+    Ok(LineFile { code, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_chars;
+
+    #[test]
+    fn test_decode_single_line_by_index() {
+        let text = "first line\nsecond, a bit longer\nthird without newline";
+        let data = write_lines(&count_chars(text), text).unwrap();
+
+        let file = read_lines(&data).unwrap();
+        assert_eq!(file.len(), 3);
+        assert_eq!(file.line(1).unwrap(), "second, a bit longer\n");
+        assert_eq!(file.line(2).unwrap(), "third without newline");
+        assert_eq!(file.line(0).unwrap(), "first line\n");
+        assert_eq!(file.decode().unwrap(), text);
+        assert_eq!(
+            file.line(3).unwrap_err(),
+            LinesError::LineOutOfRange { index: 3, lines: 3 }
+        );
+    }
+
+    #[test]
+    fn test_empty_text_has_no_lines() {
+        let data = write_lines(&HashMap::new(), "").unwrap();
+        assert!(is_line_file(&data));
+        let file = read_lines(&data).unwrap();
+        assert!(file.is_empty());
+        assert_eq!(file.decode().unwrap(), "");
+    }
+
+    #[test]
+    fn test_read_lines_rejects_truncated_and_corrupt_input() {
+        let text = "aaa\nbbb\nab\n";
+        let data = write_lines(&count_chars(text), text).unwrap();
+        for len in 0..data.len() {
+            assert!(read_lines(&data[..len]).is_err(), "length {}", len);
+        }
+
+        // The last byte holds at least one bit of the last line, so
+        // flipping all of it changes what that line decodes to.
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        let file = read_lines(&corrupt).unwrap();
+        assert_eq!(file.line(0).unwrap(), "aaa\n");
+        assert!(file.line(2).is_err());
+
+        assert_eq!(
+            write_lines(&count_chars("ab"), "abc").unwrap_err(),
+            LinesError::Encode(EncodeError::UnknownSymbol('c'))
+        );
+    }
+}
@@ -1,63 +1,757 @@
-mod huffman_encoder;
-mod huffman_tree;
-
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+
+use huffman_challenge::bits::unpack_bits;
+use huffman_challenge::blocks::is_block_file;
+use huffman_challenge::checksum::crc32;
+use huffman_challenge::counter::{count_chars, count_symbols};
+use huffman_challenge::lines::is_line_file;
+use huffman_challenge::model::{load_frequencies, save_frequencies};
+use huffman_challenge::records::is_record_file;
+use huffman_challenge::stats::entropy;
+use huffman_challenge::{
+    compress_symbols, create_counter, read_archive, read_blocks, read_container, read_lines,
+    read_records, write_adaptive_container, write_archive, write_blocks,
+    write_compressed_container, write_container, write_lines, write_records,
+    write_stored_container, AdaptiveHuffman, CompressionStats, Container, EncodeOptions,
+    FormatError, HuffmanCode, Symbol,
+};
+
+const USAGE: &str = "Usage: huffman-challenge [--sort freq|char] <file>
+       huffman-challenge compress [--mode static|adaptive] [--model <path>] [--line-mode] [--record-size <n>] [--threads <n>] [--symbol-width 8|16] [--stats] [--progress] <input> <output>
+       huffman-challenge decompress [--line <index>] [--threads <n>] <input> <output>
+       huffman-challenge verify <compressed>
+       huffman-challenge table <input>
+       huffman-challenge entropy <input>
+       huffman-challenge model <input> <model_out>
+       huffman-challenge archive <output> <files>...
+       huffman-challenge extract <archive> <dir>
+
+Use - as <input> or <output> to read from stdin or write to stdout.
+--mode static (the default) counts the input first and stores the code;
+--mode adaptive codes it in one pass and stores no table. The container
+records the mode, so decompress needs no flag.
+--stats prints the sizes, ratio and bits per symbol to stderr.
+--progress reports the bytes coded so far on stderr, out of the file size
+for a regular file; it applies to --mode static only.
+--model builds the code from a char<TAB>count model, as written by the
+model command, instead of the input's own frequencies.
+--line-mode compresses each line on its own with a shared code, so that
+decompress --line can decode a single line, counting from 0.
+--sort orders the frequency dump by descending count (freq) or by char
+(char, the default).
+--record-size compresses binary input as records of <n> bytes (1 to 256),
+with a separate code for each byte position.
+--threads splits the input into <n> blocks coded in parallel with a shared
+code; decompress decodes such files on <n> threads, by default one per core.
+--symbol-width codes binary input as 8-bit bytes or 16-bit big-endian
+pairs of bytes instead of as UTF-8 text.";
+
+const COMMANDS: [&str; 8] = [
+    "compress",
+    "decompress",
+    "verify",
+    "table",
+    "entropy",
+    "model",
+    "archive",
+    "extract",
+];
+
+/// How `compress` codes its input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Mode {
+    /// Two passes: count the input, then code it with a stored table.
+    #[default]
+    Static,
+    /// One pass with `AdaptiveHuffman`, no table.
+    Adaptive,
+}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_path = &args[1];
-    let file = File::open(file_path).unwrap();
-    let counter = create_counter(file);
-    print_char_count(&counter);
+/// Order of the rows printed for `huffman-challenge <file>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortOrder {
+    #[default]
+    Char,
+    /// Most frequent first, ties broken by char.
+    Frequency,
 }
 
-fn create_counter<R: Read>(reader: R) -> HashMap<char, i32> {
-    let reader = BufReader::new(reader);
-    let mut counter = HashMap::new();
-    for line in reader.lines() {
-        let line = line.unwrap();
-        for c in line.chars() {
-            *counter.entry(c).or_insert(0) += 1;
+/// Symbols `compress --symbol-width` splits binary input into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolWidth {
+    Bits8,
+    /// Big-endian pairs of bytes; the input must have an even length.
+    Bits16,
+}
+
+/// Flags accepted before or between the positional arguments.
+#[derive(Default)]
+struct Options {
+    mode: Mode,
+    model: Option<String>,
+    stats: bool,
+    progress: bool,
+    line_mode: bool,
+    line: Option<usize>,
+    record_size: Option<usize>,
+    sort: SortOrder,
+    threads: Option<usize>,
+    symbol_width: Option<SymbolWidth>,
+}
+
+/// The command line could not be understood. `main` prints these with the
+/// usage text and exits with status 2, like other command-line tools.
+#[derive(Debug)]
+struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            write!(f, "{}", USAGE)
+        } else {
+            write!(f, "{}\n\n{}", self.0, USAGE)
         }
     }
-    counter
 }
 
-fn print_char_count(counter: &HashMap<char, i32>) {
-    let mut sorted_keys: Vec<_> = counter.keys().collect();
-    sorted_keys.sort();
-    println!("Character Frequency:");
-    for ch in sorted_keys {
-        println!("'{}': {}", ch, counter[ch]);
+impl Error for UsageError {}
+
+fn usage(message: &str) -> Box<dyn Error> {
+    Box::new(UsageError(message.to_string()))
+}
+
+/// Splits `args` into positional arguments and `Options`.
+fn parse_args(args: &[String]) -> Result<(Vec<&str>, Options), Box<dyn Error>> {
+    let mut positional = Vec::new();
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mode" => {
+                options.mode = match args.next().map(String::as_str) {
+                    Some("static") => Mode::Static,
+                    Some("adaptive") => Mode::Adaptive,
+                    _ => return Err(usage("--mode needs static or adaptive")),
+                };
+            }
+            "--sort" => {
+                options.sort = match args.next().map(String::as_str) {
+                    Some("char") => SortOrder::Char,
+                    Some("freq") => SortOrder::Frequency,
+                    _ => return Err(usage("--sort needs freq or char")),
+                };
+            }
+            "--stats" => options.stats = true,
+            "--progress" => options.progress = true,
+            "--line-mode" => options.line_mode = true,
+            "--line" => {
+                let index = args.next().and_then(|index| index.parse().ok());
+                options.line = Some(index.ok_or_else(|| usage("--line needs a line number"))?);
+            }
+            "--threads" => {
+                let threads = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                options.threads =
+                    Some(threads.ok_or_else(|| usage("--threads needs a positive number"))?);
+            }
+            "--symbol-width" => {
+                options.symbol_width = match args.next().map(String::as_str) {
+                    Some("8") => Some(SymbolWidth::Bits8),
+                    Some("16") => Some(SymbolWidth::Bits16),
+                    _ => return Err(usage("--symbol-width needs 8 or 16")),
+                };
+            }
+            "--record-size" => {
+                let size = args.next().and_then(|size| size.parse().ok());
+                options.record_size =
+                    Some(size.ok_or_else(|| usage("--record-size needs a number of bytes"))?);
+            }
+            "--model" => {
+                let path = args.next().ok_or_else(|| usage("--model needs a path"))?;
+                options.model = Some(path.clone());
+            }
+            flag if flag.starts_with("--") => {
+                return Err(usage(&format!("unknown option {}", flag)))
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    Ok((positional, options))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<UsageError>() => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (args, options) = parse_args(&args)?;
+    match args.as_slice() {
+        ["compress", ..]
+            if options.progress
+                && (options.mode == Mode::Adaptive
+                    || options.line_mode
+                    || options.record_size.is_some()
+                    || options.threads.is_some()
+                    || options.symbol_width.is_some()) =>
+        {
+            Err(
+                "--progress cannot be combined with --mode adaptive, --line-mode, \
+                 --record-size, --threads or --symbol-width"
+                    .into(),
+            )
+        }
+        ["compress", input, output] if options.record_size.is_some() => {
+            compress_records(input, output, &options)
+        }
+        ["compress", input, output] => {
+            let stats = compress(input, output, &options)?;
+            if options.stats {
+                print_stats(&stats);
+            }
+            Ok(())
+        }
+        ["decompress", input, output] => decompress(input, output, &options),
+        ["verify", input] => verify(input),
+        ["table", input] => print_table(input),
+        ["entropy", input] => print_entropy(input),
+        ["model", input, model] => save_model(input, model),
+        ["archive", output, files @ ..] if !files.is_empty() => archive(output, files),
+        ["extract", input, dir] => extract(input, dir),
+        [command, ..] if COMMANDS.contains(command) => {
+            Err(usage(&format!("wrong arguments for {}", command)))
+        }
+        [file_path] => {
+            let file =
+                File::open(file_path).map_err(|e| format!("cannot open {}: {}", file_path, e))?;
+            let counter =
+                create_counter(file).map_err(|e| format!("cannot read {}: {}", file_path, e))?;
+            print_char_count(&counter, options.sort);
+            Ok(())
+        }
+        _ => Err(usage("")),
+    }
+}
+
+fn compress(
+    input: &str,
+    output: &str,
+    options: &Options,
+) -> Result<CompressionStats, Box<dyn Error>> {
+    if let Some(width) = options.symbol_width {
+        return compress_binary(input, output, width, options);
+    }
+    let text = String::from_utf8(read_input(input)?)
+        .map_err(|e| format!("cannot read {}: {}", input, e))?;
+    if options.line_mode {
+        if options.mode == Mode::Adaptive {
+            return Err("--line-mode only applies to --mode static".into());
+        }
+        if options.threads.is_some() {
+            return Err("--threads cannot be combined with --line-mode".into());
+        }
+        return compress_lines(&text, output, options);
+    }
+    if let Some(threads) = options.threads {
+        if options.mode == Mode::Adaptive || options.model.is_some() {
+            return Err("--threads cannot be combined with --mode adaptive or --model".into());
+        }
+        return compress_blocks(&text, output, threads);
+    }
+    if text.is_empty() {
+        // Nothing to build a code from, whatever the mode or model: write a
+        // header with an empty table, zero symbols and no payload.
+        let frequencies = HashMap::new();
+        let container = write_container::<char>(&frequencies, &[], 0, crc32(b""));
+        write_output(output, &container)?;
+        return Ok(CompressionStats::new(
+            &frequencies,
+            &HuffmanCode::new(HashMap::new()),
+            0,
+            container.len() as u64,
+        ));
+    }
+    if options.mode == Mode::Adaptive {
+        if options.model.is_some() {
+            return Err("--model only applies to --mode static".into());
+        }
+        // The adaptive coder has no code of its own; the stats describe the
+        // static code for the same input so the two modes compare directly.
+        let frequencies = count_chars(&text);
+        let code = HuffmanCode::from_frequencies(&frequencies)?;
+        let container = write_adaptive_container::<char>(text.as_bytes());
+        write_output(output, &container)?;
+        return Ok(CompressionStats::new(
+            &frequencies,
+            &code,
+            text.len() as u64,
+            container.len() as u64,
+        ));
     }
+    let frequencies = match &options.model {
+        Some(path) => load_frequencies(Path::new(path))
+            .map_err(|e| format!("cannot load model {}: {}", path, e))?,
+        None => count_chars(&text),
+    };
+    let code = HuffmanCode::from_frequencies(&frequencies)?;
+    let container = if options.progress {
+        compress_with_progress(input, &text, &frequencies, &code)?
+    } else {
+        let symbols: Vec<char> = text.chars().collect();
+        write_compressed_container(&frequencies, &code, &symbols)?
+    };
+    write_output(output, &container)?;
+    Ok(CompressionStats::new(
+        &frequencies,
+        &code,
+        text.len() as u64,
+        container.len() as u64,
+    ))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
-    use std::io::Cursor;
+/// Builds the same container as `write_compressed_container`, but codes the
+/// payload with the streaming encoder so that progress can be reported on
+/// stderr after every block. The total is the size of a regular file, and
+/// 0, for unknown, when reading stdin.
+fn compress_with_progress(
+    input: &str,
+    text: &str,
+    frequencies: &HashMap<char, u64>,
+    code: &HuffmanCode,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let total = if input == "-" {
+        0
+    } else {
+        fs::metadata(input)?.len()
+    };
+    let mut payload = Vec::new();
+    let bit_len = code.encode_stream_with_progress(
+        text.as_bytes(),
+        &mut payload,
+        &EncodeOptions::default(),
+        total,
+        |processed, total| {
+            if total == 0 {
+                eprintln!("progress:    {} bytes", processed);
+            } else {
+                eprintln!(
+                    "progress:    {} of {} bytes ({}%)",
+                    processed,
+                    total,
+                    processed * 100 / total
+                );
+            }
+        },
+    )?;
+    let bits = unpack_bits(&payload, bit_len as usize);
+    // A model may count chars the text does not have.
+    let symbol_count = text.chars().count() as u64;
+    let container = write_container(frequencies, &bits, symbol_count, crc32(text.as_bytes()));
+    // Like `write_compressed_container`, store input that does not shrink.
+    let stored = write_stored_container::<char>(text.as_bytes());
+    Ok(if stored.len() < container.len() {
+        stored
+    } else {
+        container
+    })
+}
 
-    #[test]
-    fn test_create_counter() {
-        let input_data = "hello world";
-        let fake_file = Cursor::new(input_data.as_bytes().to_vec());
+/// Compresses `text` line by line with one code, built from the model or
+/// from the whole text.
+fn compress_lines(
+    text: &str,
+    output: &str,
+    options: &Options,
+) -> Result<CompressionStats, Box<dyn Error>> {
+    let frequencies = match &options.model {
+        Some(path) => load_frequencies(Path::new(path))
+            .map_err(|e| format!("cannot load model {}: {}", path, e))?,
+        None => count_chars(text),
+    };
+    // Empty text has no lines and needs no code.
+    let code = if text.is_empty() {
+        HuffmanCode::new(HashMap::new())
+    } else {
+        HuffmanCode::from_frequencies(&frequencies)?
+    };
+    let data = write_lines(&frequencies, text)?;
+    write_output(output, &data)?;
+    Ok(CompressionStats::new(
+        &frequencies,
+        &code,
+        text.len() as u64,
+        data.len() as u64,
+    ))
+}
 
-        let counter = create_counter(fake_file);
+/// Compresses `text` as `threads` blocks coded in parallel.
+fn compress_blocks(
+    text: &str,
+    output: &str,
+    threads: usize,
+) -> Result<CompressionStats, Box<dyn Error>> {
+    let frequencies = count_chars(text);
+    let code = if text.is_empty() {
+        HuffmanCode::new(HashMap::new())
+    } else {
+        HuffmanCode::from_frequencies(&frequencies)?
+    };
+    let data = write_blocks(text, threads);
+    write_output(output, &data)?;
+    Ok(CompressionStats::new(
+        &frequencies,
+        &code,
+        text.len() as u64,
+        data.len() as u64,
+    ))
+}
 
-        let mut expected = HashMap::new();
-        expected.insert('h', 1);
-        expected.insert('e', 1);
-        expected.insert('l', 3);
-        expected.insert('o', 2);
-        expected.insert(' ', 1);
-        expected.insert('w', 1);
-        expected.insert('r', 1);
-        expected.insert('d', 1);
+/// Compresses the raw bytes of `input` as symbols of `width` bits.
+fn compress_binary(
+    input: &str,
+    output: &str,
+    width: SymbolWidth,
+    options: &Options,
+) -> Result<CompressionStats, Box<dyn Error>> {
+    if options.mode == Mode::Adaptive
+        || options.model.is_some()
+        || options.line_mode
+        || options.threads.is_some()
+    {
+        return Err(
+            "--symbol-width cannot be combined with --mode adaptive, --model, \
+                    --line-mode or --threads"
+                .into(),
+        );
+    }
+    let data = read_input(input)?;
+    match width {
+        SymbolWidth::Bits8 => compress_symbols_to(&data, data.len(), output),
+        SymbolWidth::Bits16 => {
+            if data.len() % 2 != 0 {
+                return Err(format!(
+                    "{} has an odd number of bytes, not a whole number of 16-bit symbols",
+                    input
+                )
+                .into());
+            }
+            let symbols: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            compress_symbols_to(&symbols, data.len(), output)
+        }
+    }
+}
+
+/// Writes the container `compress_symbols` picks for `symbols`, which
+/// serialize to `original_bytes` bytes.
+fn compress_symbols_to<S: Symbol>(
+    symbols: &[S],
+    original_bytes: usize,
+    output: &str,
+) -> Result<CompressionStats, Box<dyn Error>> {
+    let frequencies = count_symbols(symbols.iter().copied());
+    let code = if symbols.is_empty() {
+        HuffmanCode::new(HashMap::new())
+    } else {
+        HuffmanCode::from_frequencies(&frequencies)?
+    };
+    let container = compress_symbols(symbols, &EncodeOptions::default());
+    write_output(output, &container)?;
+    Ok(CompressionStats::new(
+        &frequencies,
+        &code,
+        original_bytes as u64,
+        container.len() as u64,
+    ))
+}
 
-        assert_eq!(counter, expected);
+/// Compresses the raw bytes of `input` in record mode. The per-position
+/// codes are not a single `HuffmanCode`, so `--stats` is not supported.
+fn compress_records(input: &str, output: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.mode == Mode::Adaptive
+        || options.model.is_some()
+        || options.line_mode
+        || options.threads.is_some()
+        || options.symbol_width.is_some()
+    {
+        return Err(
+            "--record-size cannot be combined with --mode adaptive, --model, \
+             --line-mode, --threads or --symbol-width"
+                .into(),
+        );
+    }
+    if options.stats {
+        return Err("--stats is not supported with --record-size".into());
+    }
+    let record_size = options.record_size.expect("checked by the caller");
+    let data = write_records(&read_input(input)?, record_size)?;
+    write_output(output, &data)
+}
+
+/// Decodes a container or a line-mode, record-mode or block file.
+/// `options.line` picks a single line of a line-mode file and
+/// `options.threads` the number of threads for a block file.
+fn decompress(input: &str, output: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let line = options.line;
+    let data = read_input(input)?;
+    if is_block_file(&data) && line.is_none() {
+        let threads = options.threads.unwrap_or_else(default_threads);
+        return write_output(output, read_blocks(&data, threads)?.as_bytes());
+    }
+    if is_record_file(&data) {
+        if line.is_some() {
+            return Err(format!("{} was not compressed with --line-mode", input).into());
+        }
+        return write_output(output, &read_records(&data)?);
+    }
+    let text = if is_line_file(&data) {
+        let lines = read_lines(&data)?;
+        match line {
+            Some(index) => lines.line(index)?,
+            None => lines.decode()?,
+        }
+    } else if line.is_some() {
+        return Err(format!("{} was not compressed with --line-mode", input).into());
+    } else {
+        match read_container::<char>(&data) {
+            Err(FormatError::SymbolKindMismatch(kind)) => {
+                return write_output(output, &decode_binary(&data, kind)?);
+            }
+            container => container?.decode()?,
+        }
+    };
+    write_output(output, text.as_bytes())
+}
+
+/// Decodes a container of binary symbols, as `compress --symbol-width`
+/// writes them, back to the raw bytes.
+fn decode_binary(data: &[u8], kind: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+    if kind == u8::KIND {
+        Ok(read_container::<u8>(data)?.decode_symbols()?)
+    } else if kind == u16::KIND {
+        let symbols = read_container::<u16>(data)?.decode_symbols()?;
+        Ok(symbols.iter().flat_map(|s| s.to_be_bytes()).collect())
+    } else {
+        Err(FormatError::SymbolKindMismatch(kind).into())
+    }
+}
+
+/// Threads to decode block files on without `--threads`: one per core.
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Reports `stats` on stderr, so stdout stays free for `-` output.
+fn print_stats(stats: &CompressionStats) {
+    eprintln!("original:    {} bytes", stats.original_bytes);
+    eprintln!("compressed:  {} bytes", stats.compressed_bytes);
+    eprintln!("ratio:       {:.3}", stats.ratio);
+    eprintln!(
+        "bits/symbol: {:.3} (entropy {:.3})",
+        stats.bits_per_symbol, stats.entropy
+    );
+}
+
+/// Decodes a compressed file in memory, checking its checksums. A
+/// line-mode file is checked line by line against the checksum of each,
+/// a block or record file against the checksum of the whole data.
+fn verify(input: &str) -> Result<(), Box<dyn Error>> {
+    let data = read_input(input)?;
+    let decoded_bytes = if is_line_file(&data) {
+        read_lines(&data)?.decode()?.len()
+    } else if is_block_file(&data) {
+        read_blocks(&data, default_threads())?.len()
+    } else if is_record_file(&data) {
+        read_records(&data)?.len()
+    } else {
+        // Containers of bytes or 16-bit symbols, as `--symbol-width`
+        // writes them, record their kind in the header.
+        match read_container::<char>(&data) {
+            Err(FormatError::SymbolKindMismatch(kind)) if kind == u8::KIND => {
+                verify_container(input, read_container::<u8>(&data)?)?
+            }
+            Err(FormatError::SymbolKindMismatch(kind)) if kind == u16::KIND => {
+                verify_container(input, read_container::<u16>(&data)?)?
+            }
+            container => verify_container(input, container?)?,
+        }
+    };
+    println!("{}: OK ({} bytes decoded)", input, decoded_bytes);
+    Ok(())
+}
+
+/// Decodes a container, checking the stored checksum, and re-encodes the
+/// result with the same code to confirm the payload survives the trip.
+/// Returns the number of bytes the symbols serialize to.
+fn verify_container<S: Symbol + 'static>(
+    input: &str,
+    container: Container<S>,
+) -> Result<usize, Box<dyn Error>> {
+    let symbols = container.decode_symbols()?;
+    let mut bytes = Vec::new();
+    for symbol in &symbols {
+        symbol.write_bytes(&mut bytes);
+    }
+    let round_trips = match &container {
+        Container::Huffman { code, bits, .. } => {
+            &code.encode_symbols(symbols.iter().copied())? == bits
+        }
+        Container::Adaptive { payload, .. } => {
+            let mut encoded = Vec::new();
+            AdaptiveHuffman::encode(bytes.as_slice(), &mut encoded)?;
+            &encoded == payload
+        }
+        Container::Transformed {
+            transform, inner, ..
+        } => transform.apply(&bytes) == inner.decode_symbols()?,
+        Container::Stored { .. } => true,
+    };
+    if !round_trips {
+        return Err(format!("{} does not round-trip", input).into());
+    }
+    Ok(bytes.len())
+}
+
+/// Prints the entropy of `input`'s char distribution and the size no
+/// coder that codes chars independently can beat: entropy times the number
+/// of chars, rounded up to whole bytes. The table is not counted.
+fn print_entropy(input: &str) -> Result<(), Box<dyn Error>> {
+    let text = String::from_utf8(read_input(input)?)
+        .map_err(|e| format!("cannot read {}: {}", input, e))?;
+    let frequencies = count_chars(&text);
+    let bits_per_symbol = entropy(&frequencies);
+    let symbols: u64 = frequencies.values().sum();
+    let minimum_bytes = (bits_per_symbol * symbols as f64 / 8.0).ceil() as u64;
+    println!("entropy:     {:.3} bits/symbol", bits_per_symbol);
+    println!("symbols:     {}", symbols);
+    println!(
+        "minimum:     {} bytes (input is {} bytes)",
+        minimum_bytes,
+        text.len()
+    );
+    Ok(())
+}
+
+/// Prints the code built for `input`: every symbol with its frequency, code
+/// length and bit pattern, most frequent first.
+fn print_table(input: &str) -> Result<(), Box<dyn Error>> {
+    let text = String::from_utf8(read_input(input)?)
+        .map_err(|e| format!("cannot read {}: {}", input, e))?;
+    let frequencies = count_chars(&text);
+    let code = HuffmanCode::from_frequencies(&frequencies)?;
+
+    let mut rows = code.codes_sorted();
+    rows.sort_by(|(a, _), (b, _)| frequencies[b].cmp(&frequencies[a]).then(a.cmp(b)));
+    println!("{:<8} {:>9} {:>6}  Code", "Symbol", "Frequency", "Length");
+    for (c, bits) in rows {
+        let pattern: String = bits
+            .iter()
+            .map(|&bit| if bit { '1' } else { '0' })
+            .collect();
+        println!(
+            "{:<8} {:>9} {:>6}  {}",
+            format!("{:?}", c),
+            frequencies[&c],
+            bits.len(),
+            pattern
+        );
+    }
+    Ok(())
+}
+
+/// Counts the chars of `input` and saves them as a reusable model.
+fn save_model(input: &str, model: &str) -> Result<(), Box<dyn Error>> {
+    let text = String::from_utf8(read_input(input)?)
+        .map_err(|e| format!("cannot read {}: {}", input, e))?;
+    save_frequencies(&count_chars(&text), Path::new(model))
+        .map_err(|e| format!("cannot write {}: {}", model, e))?;
+    Ok(())
+}
+
+/// Bundles `files` into one archive, each stored under its file name.
+fn archive(output: &str, files: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut contents = Vec::new();
+    for &file in files {
+        let name = Path::new(file)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{} has no usable file name", file))?;
+        if contents.iter().any(|(other, _)| other == &name) {
+            return Err(format!("{} appears twice in the archive", name).into());
+        }
+        contents.push((name, read_input(file)?));
+    }
+    let archive = write_archive(contents.iter().map(|(name, data)| (*name, data.as_slice())))?;
+    write_output(output, &archive)
+}
+
+/// Writes every file of an archive into `dir`, creating it if needed.
+fn extract(input: &str, dir: &str) -> Result<(), Box<dyn Error>> {
+    let entries = read_archive(&read_input(input)?)?;
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir, e))?;
+    for entry in entries {
+        let path = Path::new(dir).join(&entry.name);
+        fs::write(&path, &entry.data)
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Reads the whole input file, or stdin when `path` is `-`.
+fn read_input(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let result = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path)
+    };
+    Ok(result.map_err(|e| format!("cannot read {}: {}", path, e))?)
+}
+
+/// Writes `data` to the output file, or to stdout when `path` is `-`.
+fn write_output(path: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let result = if path == "-" {
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        stdout.write_all(data).and_then(|_| stdout.flush())
+    } else {
+        fs::write(path, data)
+    };
+    Ok(result.map_err(|e| format!("cannot write {}: {}", path, e))?)
+}
+
+/// Prints every char with its count. Chars are shown as Rust char literals,
+/// so control chars read as `'\n'`, `'\t'` or `'\0'` and combining marks,
+/// which would otherwise join the quote before them, as their codepoint,
+/// like `'\u{301}'`.
+fn print_char_count(counter: &HashMap<char, u64>, order: SortOrder) {
+    let mut sorted_keys: Vec<_> = counter.keys().collect();
+    match order {
+        SortOrder::Char => sorted_keys.sort(),
+        SortOrder::Frequency => {
+            sorted_keys.sort_by(|a, b| counter[*b].cmp(&counter[*a]).then(a.cmp(b)))
+        }
+    }
+    println!("Character Frequency:");
+    for ch in sorted_keys {
+        println!("{:?}: {}", ch, counter[ch]);
     }
 }
@@ -0,0 +1,90 @@
+//! Read-only memory maps of regular files, for the `mmap` feature.
+//!
+//! Counting a mapped file reads the page cache directly instead of copying
+//! every chunk through a `BufReader`. The crate has no dependencies, so this
+//! calls `mmap(2)` itself; it is only built on 64-bit Unix, where `off_t` is
+//! 64 bits wide.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+
+const PROT_READ: c_int = 1;
+const MAP_PRIVATE: c_int = 2;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// A file mapped read-only into memory, unmapped on drop.
+///
+/// The bytes are only stable while nobody else writes to the file; like any
+/// memory map, truncating the file while it is mapped can fault.
+#[derive(Debug)]
+pub(crate) struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps all of `file`. Gives `None` for anything that cannot be mapped
+    /// as a whole, such as pipes, terminals or empty files, so callers can
+    /// fall back to reading it.
+    pub(crate) fn map(file: &File) -> io::Result<Option<Self>> {
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return Ok(None);
+        }
+        let Ok(len) = usize::try_from(metadata.len()) else {
+            return Ok(None);
+        };
+        // SAFETY: a fresh private read-only mapping of a file we hold open;
+        // the result is checked for MAP_FAILED before use.
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Some(Self { ptr, len }))
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is `len` readable bytes and lives as long as
+        // `self`.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` are exactly what `mmap` returned and the
+        // mapping is not used after this.
+        unsafe {
+            munmap(self.ptr, self.len);
+        }
+    }
+}
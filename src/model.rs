@@ -0,0 +1,144 @@
+//! Frequency models stored as text, one `char<TAB>count` line per symbol.
+//!
+//! Tab, newline, carriage return and backslash are written as `\t`, `\n`,
+//! `\r` and `\\` so that every entry stays on its own line.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Loads a frequency model written in the `char<TAB>count` format.
+/// Malformed lines, repeated chars and invalid counts are reported as
+/// `InvalidData` with the offending line number.
+pub fn load_frequencies(path: &Path) -> io::Result<HashMap<char, u64>> {
+    parse_frequencies(&fs::read_to_string(path)?)
+}
+
+/// Writes `frequencies` to `path` in the format `load_frequencies` reads,
+/// one line per char in char order so the output is deterministic.
+pub fn save_frequencies(frequencies: &HashMap<char, u64>, path: &Path) -> io::Result<()> {
+    fs::write(path, format_frequencies(frequencies))
+}
+
+fn format_frequencies(frequencies: &HashMap<char, u64>) -> String {
+    let mut entries: Vec<_> = frequencies.iter().collect();
+    entries.sort();
+    let mut text = String::new();
+    for (&c, count) in entries {
+        match c {
+            '\t' => text.push_str("\\t"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\\' => text.push_str("\\\\"),
+            c => text.push(c),
+        }
+        text.push('\t');
+        text.push_str(&count.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+fn parse_frequencies(text: &str) -> io::Result<HashMap<char, u64>> {
+    let mut frequencies = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("model line {}: {}", i + 1, reason),
+            )
+        };
+        if line.is_empty() {
+            continue;
+        }
+        let (symbol, count) = line
+            .split_once('\t')
+            .ok_or_else(|| invalid("expected <char><TAB><count>"))?;
+        let c = unescape(symbol).ok_or_else(|| invalid("expected a single char"))?;
+        let count: u64 = count
+            .parse()
+            .map_err(|_| invalid("count is not a non-negative integer"))?;
+        if frequencies.insert(c, count).is_some() {
+            return Err(invalid("char appears twice"));
+        }
+    }
+    Ok(frequencies)
+}
+
+fn unescape(symbol: &str) -> Option<char> {
+    let mut chars = symbol.chars();
+    let c = match (chars.next()?, chars.next()) {
+        ('\\', Some(escaped)) => match escaped {
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            '\\' => '\\',
+            _ => return None,
+        },
+        (c, None) => c,
+        _ => return None,
+    };
+    chars.next().is_none().then_some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::huffman_encoder::HuffmanCode;
+
+    #[test]
+    fn test_load_model_and_build_code() {
+        let path = std::env::temp_dir().join("huffman_challenge_load_model.tsv");
+        fs::write(&path, "a\t5\n\\n\t2\n\\t\t1\né\t3\n").unwrap();
+
+        let frequencies = load_frequencies(&path).unwrap();
+        assert_eq!(
+            frequencies,
+            HashMap::from([('a', 5), ('\n', 2), ('\t', 1), ('é', 3)])
+        );
+
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let text = "a\té\na";
+        assert_eq!(code.decode(&code.encode(text).unwrap()).unwrap(), text);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path = std::env::temp_dir().join("huffman_challenge_save_model.tsv");
+        let frequencies = HashMap::from([
+            ('a', 7),
+            ('\t', 1),
+            ('\n', 4),
+            ('\r', 4),
+            ('\\', 2),
+            (' ', 9),
+            ('🎉', 3),
+        ]);
+
+        save_frequencies(&frequencies, &path).unwrap();
+        assert_eq!(load_frequencies(&path).unwrap(), frequencies);
+        fs::remove_file(&path).unwrap();
+
+        let single = HashMap::from([('b', 2), ('a', 1)]);
+        assert_eq!(format_frequencies(&single), "a\t1\nb\t2\n");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lines() {
+        for text in [
+            "a 5\n",
+            "ab\t5\n",
+            "a\t-1\n",
+            "a\tx\n",
+            "a\t1\na\t2\n",
+            "\\q\t1\n",
+        ] {
+            let err = parse_frequencies(text).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+        let err = parse_frequencies("a\t1\nb 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+}
@@ -0,0 +1,121 @@
+//! A finished code that can only be used, not inspected or rebuilt.
+
+use std::collections::HashMap;
+
+use crate::huffman_encoder::{DecodeError, EncodeError, FormatError, HuffmanCode};
+use crate::symbol::Symbol;
+
+/// A `HuffmanCode` frozen for use: it can encode, decode and serialize, but
+/// gives no access to the code it wraps, so callers handed a `SealedCode`
+/// cannot depend on how it was built or on its table layout:
+///
+/// ```
+/// use huffman_challenge::{HuffmanCode, SealedCode};
+///
+/// let code = HuffmanCode::from_reader("abracadabra".as_bytes()).unwrap();
+/// let sealed = SealedCode::new(code);
+/// let bits = sealed.encode("cab").unwrap();
+/// assert_eq!(sealed.decode(&bits).unwrap(), "cab");
+/// ```
+///
+/// There is no way back to the table:
+///
+/// ```compile_fail
+/// use huffman_challenge::{HuffmanCode, SealedCode};
+///
+/// let code = HuffmanCode::from_reader("abracadabra".as_bytes()).unwrap();
+/// let mut sealed = SealedCode::new(code);
+/// let table = &mut sealed.0;
+/// ```
+#[derive(Debug)]
+pub struct SealedCode<S = char>(HuffmanCode<S>);
+
+impl<S: Symbol> SealedCode<S> {
+    pub fn new(code: HuffmanCode<S>) -> Self {
+        Self(code)
+    }
+
+    /// Reads a code written by `serialize`.
+    pub fn deserialize(data: &[u8]) -> Result<Self, FormatError> {
+        HuffmanCode::deserialize_codes(data).map(Self)
+    }
+
+    pub fn encode_symbols<I>(&self, symbols: I) -> Result<Vec<bool>, EncodeError<S>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        self.0.encode_symbols(symbols)
+    }
+
+    pub fn decode_symbols(&self, data: &[bool]) -> Result<Vec<S>, DecodeError> {
+        self.0.decode_symbols(data)
+    }
+
+    /// A copy of the code lengths; changing it does not affect the code.
+    pub fn code_lengths(&self) -> HashMap<S, u8> {
+        self.0.code_lengths()
+    }
+
+    /// Serializes the full table, like `HuffmanCode::serialize_codes`.
+    /// Lengths alone would only give back the canonical code for them, and
+    /// a sealed code need not be canonical.
+    pub fn serialize(&self) -> Vec<u8> {
+        self.0.serialize_codes()
+    }
+}
+
+impl SealedCode<char> {
+    pub fn encode(&self, data: &str) -> Result<Vec<bool>, EncodeError> {
+        self.0.encode(data)
+    }
+
+    pub fn decode(&self, data: &[bool]) -> Result<String, DecodeError> {
+        self.0.decode(data)
+    }
+}
+
+impl<S: Symbol> From<HuffmanCode<S>> for SealedCode<S> {
+    fn from(code: HuffmanCode<S>) -> Self {
+        Self::new(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_chars;
+
+    #[test]
+    fn test_sealed_code_encodes_decodes_and_serializes() {
+        let text = "sealed codes still code";
+        let code = HuffmanCode::from_frequencies(&count_chars(text)).unwrap();
+        let expected_bits = code.encode(text).unwrap();
+        let sealed = SealedCode::from(HuffmanCode::canonical(&code.code_lengths()));
+
+        let bits = sealed.encode(text).unwrap();
+        assert_eq!(bits.len(), expected_bits.len());
+        assert_eq!(sealed.decode(&bits).unwrap(), text);
+        assert_eq!(sealed.encode("z"), Err(EncodeError::UnknownSymbol('z')));
+
+        // The lengths are a copy: changing them leaves the code as it was.
+        let mut lengths = sealed.code_lengths();
+        lengths.clear();
+        assert_eq!(sealed.code_lengths(), code.code_lengths());
+
+        let restored = SealedCode::<char>::deserialize(&sealed.serialize()).unwrap();
+        assert_eq!(restored.encode(text).unwrap(), bits);
+    }
+
+    #[test]
+    fn test_non_canonical_code_survives_serialization() {
+        let text = "abracadabra, said the sealed code";
+        let code = HuffmanCode::from_frequencies(&count_chars(text)).unwrap();
+        let canonical = HuffmanCode::canonical(&code.code_lengths());
+        assert_ne!(code.codes_sorted(), canonical.codes_sorted());
+
+        let sealed = SealedCode::new(code);
+        let bits = sealed.encode(text).unwrap();
+        let restored = SealedCode::<char>::deserialize(&sealed.serialize()).unwrap();
+        assert_eq!(restored.decode(&bits).unwrap(), text);
+    }
+}
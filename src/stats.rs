@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::huffman_encoder::HuffmanCode;
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionStats {
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+    /// Compressed size divided by original size; below 1.0 means the output
+    /// is smaller than the input.
+    pub ratio: f64,
+    /// Average code length, weighted by symbol frequency.
+    pub bits_per_symbol: f64,
+    /// Shannon entropy of the frequency distribution, in bits per symbol.
+    pub entropy: f64,
+    /// Total of the frequencies, i.e. the number of symbols coded.
+    pub symbol_count: u64,
+}
+
+impl CompressionStats {
+    pub fn new<S: Symbol>(
+        frequencies: &HashMap<S, u64>,
+        code: &HuffmanCode<S>,
+        original_bytes: u64,
+        compressed_bytes: u64,
+    ) -> Self {
+        let ratio = if original_bytes == 0 {
+            0.0
+        } else {
+            compressed_bytes as f64 / original_bytes as f64
+        };
+        Self {
+            original_bytes,
+            compressed_bytes,
+            ratio,
+            bits_per_symbol: average_code_length(frequencies, code),
+            entropy: entropy(frequencies),
+            symbol_count: frequencies.values().sum(),
+        }
+    }
+
+    /// Bits lost to whole-bit code lengths: `(bits_per_symbol - entropy)`
+    /// times the number of symbols, i.e. how far the payload is above the
+    /// entropy bound. Never negative for a Huffman code, up to rounding.
+    pub fn overhead_bits(&self) -> f64 {
+        (self.bits_per_symbol - self.entropy) * self.symbol_count as f64
+    }
+}
+
+/// Shannon entropy `-sum(p * log2(p))` of the distribution given by
+/// `frequencies`, in bits per symbol.
+pub fn entropy<S: Symbol>(frequencies: &HashMap<S, u64>) -> f64 {
+    let total: f64 = frequencies.values().map(|&count| count as f64).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    frequencies
+        .values()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn average_code_length<S: Symbol>(frequencies: &HashMap<S, u64>, code: &HuffmanCode<S>) -> f64 {
+    let lengths = code.code_lengths();
+    let total: f64 = frequencies.values().map(|&count| count as f64).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let bits: f64 = frequencies
+        .iter()
+        .map(|(c, &count)| count as f64 * lengths.get(c).copied().unwrap_or(0) as f64)
+        .sum();
+    bits / total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 1e-9;
+
+    #[test]
+    fn test_dyadic_distribution_stats() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 8);
+        frequencies.insert('b', 4);
+        frequencies.insert('c', 2);
+        frequencies.insert('d', 2);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let stats = CompressionStats::new(&frequencies, &code, 16, 4);
+        // With power-of-two probabilities Huffman reaches the entropy exactly.
+        assert!((stats.entropy - 1.75).abs() < TOLERANCE);
+        assert!((stats.bits_per_symbol - 1.75).abs() < TOLERANCE);
+        assert!((stats.ratio - 0.25).abs() < TOLERANCE);
+        assert_eq!(stats.symbol_count, 16);
+        assert!(stats.overhead_bits().abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn test_non_dyadic_distribution_stats() {
+        let mut frequencies = HashMap::new();
+        frequencies.insert('a', 1);
+        frequencies.insert('b', 1);
+        frequencies.insert('c', 1);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+
+        let stats = CompressionStats::new(&frequencies, &code, 3, 1);
+        assert!((stats.entropy - 3f64.log2()).abs() < TOLERANCE);
+        assert!((stats.bits_per_symbol - 5.0 / 3.0).abs() < TOLERANCE);
+        assert!(stats.bits_per_symbol >= stats.entropy);
+    }
+
+    #[test]
+    fn test_overhead_bits_of_known_distribution() {
+        // p = 3/4 and 1/4: entropy is 2 - (3/4)log2(3) bits per symbol, but
+        // each symbol needs a whole bit.
+        let frequencies = HashMap::from([('a', 300), ('b', 100)]);
+        let code = HuffmanCode::from_frequencies(&frequencies).unwrap();
+        let stats = CompressionStats::new(&frequencies, &code, 400, 50);
+
+        let entropy = 2.0 - 0.75 * 3f64.log2();
+        assert!((stats.entropy - entropy).abs() < TOLERANCE);
+        assert!((stats.bits_per_symbol - 1.0).abs() < TOLERANCE);
+        let expected = (1.0 - entropy) * 400.0;
+        assert!((stats.overhead_bits() - expected).abs() < 1e-6);
+        assert!((stats.overhead_bits() - 75.49).abs() < 0.01);
+    }
+}
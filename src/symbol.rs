@@ -0,0 +1,229 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{self, Read};
+
+use crate::huffman_encoder::FormatError;
+
+/// A value that can be counted, placed in a Huffman tree and written to a
+/// container. Implemented for `char` (text), `u8` (raw bytes) and `u16`
+/// (paired bytes, such as UTF-16 text).
+pub trait Symbol: Copy + Eq + Ord + Hash + Debug {
+    /// Identifies the symbol type in the container header.
+    const KIND: u8;
+
+    fn write_bytes(self, out: &mut Vec<u8>);
+
+    /// Reads one symbol from the start of `data`, returning it together with
+    /// the number of bytes consumed.
+    fn read_bytes(data: &[u8]) -> Result<(Self, usize), FormatError>;
+}
+
+impl Symbol for char {
+    const KIND: u8 = 0;
+
+    fn write_bytes(self, out: &mut Vec<u8>) {
+        let mut utf8 = [0u8; 4];
+        out.extend_from_slice(self.encode_utf8(&mut utf8).as_bytes());
+    }
+
+    fn read_bytes(data: &[u8]) -> Result<(Self, usize), FormatError> {
+        let lead = *data.first().ok_or(FormatError::Truncated)?;
+        let width = match lead {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Err(FormatError::InvalidSymbol),
+        };
+        let bytes = data.get(..width).ok_or(FormatError::Truncated)?;
+        let s = std::str::from_utf8(bytes).map_err(|_| FormatError::InvalidSymbol)?;
+        Ok((s.chars().next().unwrap(), width))
+    }
+}
+
+impl Symbol for u8 {
+    const KIND: u8 = 1;
+
+    fn write_bytes(self, out: &mut Vec<u8>) {
+        out.push(self);
+    }
+
+    fn read_bytes(data: &[u8]) -> Result<(Self, usize), FormatError> {
+        let byte = *data.first().ok_or(FormatError::Truncated)?;
+        Ok((byte, 1))
+    }
+}
+
+/// Written big-endian, so the serialized symbols of big-endian paired data
+/// are the data itself.
+impl Symbol for u16 {
+    const KIND: u8 = 2;
+
+    fn write_bytes(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn read_bytes(data: &[u8]) -> Result<(Self, usize), FormatError> {
+        let bytes = data.get(..2).ok_or(FormatError::Truncated)?;
+        Ok((u16::from_be_bytes([bytes[0], bytes[1]]), 2))
+    }
+}
+
+/// Iterator over the chars of UTF-8 text read from `R`, a buffer at a
+/// time. A char split across two reads is held back until the rest of it
+/// arrives, so readers may return any number of bytes per call.
+///
+/// Yields `InvalidData` for bytes that are not UTF-8 and for input ending
+/// partway through a char, and stops after the first error.
+pub struct Symbols<R> {
+    reader: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    bytes_read: u64,
+    done: bool,
+}
+
+impl<R: Read> Symbols<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(8 * 1024, reader)
+    }
+
+    /// Reads up to `capacity` bytes per call, but always at least enough
+    /// to hold one char.
+    pub fn with_capacity(capacity: usize, reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; capacity.max(4)],
+            start: 0,
+            end: 0,
+            bytes_read: 0,
+            done: false,
+        }
+    }
+
+    /// Number of bytes read from the reader so far, including bytes of
+    /// chars not yet yielded.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    fn fail(&mut self, message: &str) -> Option<io::Result<char>> {
+        self.done = true;
+        Some(Err(io::Error::new(io::ErrorKind::InvalidData, message)))
+    }
+}
+
+impl<R: Read> Iterator for Symbols<R> {
+    type Item = io::Result<char>;
+
+    fn next(&mut self) -> Option<io::Result<char>> {
+        while !self.done {
+            match char::read_bytes(&self.buf[self.start..self.end]) {
+                Ok((c, width)) => {
+                    self.start += width;
+                    return Some(Ok(c));
+                }
+                Err(FormatError::Truncated) => {}
+                Err(_) => return self.fail("input is not valid UTF-8"),
+            }
+
+            // Keep the start of a split char and read the rest after it.
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) if self.end == 0 => self.done = true,
+                Ok(0) => return self.fail("input ends with an incomplete UTF-8 sequence"),
+                Ok(n) => {
+                    self.end += n;
+                    self.bytes_read += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_utf8_widths_round_trip() {
+        for (c, width) in [('e', 1), ('é', 2), ('€', 3), ('🎉', 4)] {
+            let mut out = Vec::new();
+            c.write_bytes(&mut out);
+            assert_eq!(out.len(), width);
+            assert_eq!(out, c.to_string().into_bytes());
+
+            out.push(b'x');
+            assert_eq!(char::read_bytes(&out), Ok((c, width)));
+        }
+    }
+
+    #[test]
+    fn test_char_read_rejects_invalid_utf8() {
+        assert_eq!(char::read_bytes(&[0xFF]), Err(FormatError::InvalidSymbol));
+        assert_eq!(char::read_bytes(&[0x80]), Err(FormatError::InvalidSymbol));
+        assert_eq!(char::read_bytes(&[0xF0, 0x9F]), Err(FormatError::Truncated));
+        assert_eq!(char::read_bytes(&[]), Err(FormatError::Truncated));
+    }
+
+    #[test]
+    fn test_u16_is_big_endian() {
+        let mut out = Vec::new();
+        0x1234u16.write_bytes(&mut out);
+        assert_eq!(out, [0x12, 0x34]);
+        assert_eq!(u16::read_bytes(&[0xAB, 0xCD, 0xEF]), Ok((0xABCD, 2)));
+        assert_eq!(u16::read_bytes(&[0xAB]), Err(FormatError::Truncated));
+    }
+
+    /// Hands out one byte per `read`, so every multi-byte char is split.
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.is_empty()) {
+                (Some((&byte, rest)), false) => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_symbols_reassemble_chars_split_across_reads() {
+        let text = "aé€🎉 mixed ünï 你好\n";
+        let chars: Vec<char> = Symbols::new(OneByteReader(text.as_bytes()))
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(chars, text.chars().collect::<Vec<_>>());
+
+        // A buffer too small for a 4-byte char still makes progress.
+        let mut symbols = Symbols::with_capacity(1, text.as_bytes());
+        let chars: String = symbols.by_ref().map(Result::unwrap).collect();
+        assert_eq!(chars, text);
+        assert_eq!(symbols.bytes_read(), text.len() as u64);
+    }
+
+    #[test]
+    fn test_symbols_reject_invalid_utf8() {
+        for data in [&b"ab\xFFcd"[..], b"ab\xC3(", b"ab\xF0\x9F"] {
+            let mut symbols = Symbols::new(OneByteReader(data));
+            assert_eq!(symbols.next().unwrap().unwrap(), 'a');
+            assert_eq!(symbols.next().unwrap().unwrap(), 'b');
+            let err = symbols.next().unwrap().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", data);
+            assert!(symbols.next().is_none());
+        }
+    }
+}
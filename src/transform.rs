@@ -0,0 +1,203 @@
+//! Reversible byte transforms applied before Huffman coding.
+//!
+//! Huffman coding only looks at how often each symbol occurs, not at the
+//! order they come in. A transform rewrites the data so that structure the
+//! coder cannot see shows up in the symbol frequencies instead. Containers
+//! record the transform, see `EncodeOptions::transform`.
+
+use crate::huffman_encoder::{DecodeError, FormatError};
+
+/// A transform of the serialized symbols, stored as one byte in the header
+/// of a `BlockType::Transformed` container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Run-length coding: two equal bytes are followed by a count of how
+    /// many more times the byte repeats, so a run of up to 257 bytes takes
+    /// three. Data without runs is unchanged apart from pairs, which gain a
+    /// zero count.
+    RunLength = 0,
+    /// Move-to-front coding: every byte is replaced by its position in a
+    /// list of all 256 values, which then moves it to the front. Recently
+    /// seen bytes get small positions, so data with local repetition turns
+    /// into mostly zeros and other small values.
+    MoveToFront = 1,
+}
+
+impl TryFrom<u8> for Transform {
+    type Error = FormatError;
+
+    fn try_from(byte: u8) -> Result<Self, FormatError> {
+        match byte {
+            0 => Ok(Transform::RunLength),
+            1 => Ok(Transform::MoveToFront),
+            _ => Err(FormatError::UnknownTransform(byte)),
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Transform::RunLength => run_length_encode(data),
+            Transform::MoveToFront => move_to_front_encode(data),
+        }
+    }
+
+    /// Undoes `apply`. Data that `apply` cannot have produced is an invalid
+    /// bitstream.
+    pub fn invert(self, data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        self.invert_limited(data, u64::MAX)
+    }
+
+    /// Like `invert`, but fails with `DecodeError::OutputLimitExceeded` as
+    /// soon as the output would be longer than `max_output_bytes`.
+    pub fn invert_limited(
+        self,
+        data: &[u8],
+        max_output_bytes: u64,
+    ) -> Result<Vec<u8>, DecodeError> {
+        match self {
+            Transform::RunLength => run_length_decode(data, max_output_bytes),
+            Transform::MoveToFront if data.len() as u64 > max_output_bytes => {
+                Err(DecodeError::OutputLimitExceeded {
+                    limit: max_output_bytes,
+                })
+            }
+            Transform::MoveToFront => Ok(move_to_front_decode(data)),
+        }
+    }
+}
+
+/// Longest run a pair and its count byte can describe.
+const MAX_RUN: usize = 2 + u8::MAX as usize;
+
+fn run_length_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while let Some(&byte) = rest.first() {
+        let run = rest.iter().take_while(|&&b| b == byte).count();
+        rest = &rest[run..];
+        let mut left = run;
+        while left >= 2 {
+            let chunk = left.min(MAX_RUN);
+            out.extend_from_slice(&[byte, byte, (chunk - 2) as u8]);
+            left -= chunk;
+        }
+        if left == 1 {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+fn run_length_decode(data: &[u8], limit: u64) -> Result<Vec<u8>, DecodeError> {
+    let mut out = Vec::with_capacity(data.len().min(usize::try_from(limit).unwrap_or(usize::MAX)));
+    let mut bytes = data.iter().copied();
+    let mut previous = None;
+    while let Some(byte) = bytes.next() {
+        let count = if previous == Some(byte) {
+            Some(bytes.next().ok_or(DecodeError::InvalidBitstream)?)
+        } else {
+            None
+        };
+        let run = 1 + count.unwrap_or(0) as u64;
+        if out.len() as u64 + run > limit {
+            return Err(DecodeError::OutputLimitExceeded { limit });
+        }
+        out.extend(std::iter::repeat_n(byte, run as usize));
+        if count.is_some() {
+            // The count ends the run; the next byte starts a new one.
+            previous = None;
+        } else {
+            previous = Some(byte);
+        }
+    }
+    Ok(out)
+}
+
+/// The move-to-front list before any byte was seen: every value in order.
+fn initial_list() -> [u8; 256] {
+    std::array::from_fn(|i| i as u8)
+}
+
+fn move_to_front_encode(data: &[u8]) -> Vec<u8> {
+    let mut list = initial_list();
+    data.iter()
+        .map(|&byte| {
+            let position = list
+                .iter()
+                .position(|&b| b == byte)
+                .expect("the list holds every byte");
+            list.copy_within(..position, 1);
+            list[0] = byte;
+            position as u8
+        })
+        .collect()
+}
+
+fn move_to_front_decode(data: &[u8]) -> Vec<u8> {
+    let mut list = initial_list();
+    data.iter()
+        .map(|&position| {
+            let position = position as usize;
+            let byte = list[position];
+            list.copy_within(..position, 1);
+            list[0] = byte;
+            byte
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_length_round_trips() {
+        let long_run = [vec![7u8; 600], vec![8], vec![7; 258]].concat();
+        let cases: [&[u8]; 6] = [
+            b"",
+            b"a",
+            b"aa",
+            b"aaaaaabbbbbb",
+            b"abcabc no runs here",
+            &long_run,
+        ];
+        for data in cases {
+            let encoded = Transform::RunLength.apply(data);
+            assert_eq!(Transform::RunLength.invert(&encoded).unwrap(), data);
+        }
+        assert_eq!(Transform::RunLength.apply(b"aaaaaabbbbbb"), b"aa\x04bb\x04");
+        assert_eq!(Transform::RunLength.apply(b"abc"), b"abc");
+        assert_eq!(Transform::RunLength.apply(&[0; 257]), [0, 0, 255]);
+    }
+
+    #[test]
+    fn test_move_to_front_round_trips() {
+        let all_bytes: Vec<u8> = (0..=255).rev().chain(0..=255).collect();
+        let cases: [&[u8]; 4] = [b"", b"banana", b"aaabbbaaaccc", &all_bytes];
+        for data in cases {
+            let encoded = Transform::MoveToFront.apply(data);
+            assert_eq!(encoded.len(), data.len());
+            assert_eq!(Transform::MoveToFront.invert(&encoded).unwrap(), data);
+        }
+        assert_eq!(
+            Transform::MoveToFront.apply(b"banana"),
+            [b'b', b'a' + 1, b'n', 1, 1, 1]
+        );
+        assert_eq!(Transform::MoveToFront.apply(b"aaab"), [b'a', 0, 0, b'b']);
+    }
+
+    #[test]
+    fn test_run_length_rejects_missing_count() {
+        assert_eq!(
+            Transform::RunLength.invert(b"abb"),
+            Err(DecodeError::InvalidBitstream)
+        );
+        assert_eq!(Transform::try_from(1).unwrap(), Transform::MoveToFront);
+        assert_eq!(
+            Transform::try_from(9).unwrap_err(),
+            FormatError::UnknownTransform(9)
+        );
+    }
+}
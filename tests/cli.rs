@@ -0,0 +1,697 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn huffman() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_huffman-challenge"))
+}
+
+#[test]
+fn test_missing_file_reports_path() {
+    let output = huffman().arg("does/not/exist.txt").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cannot open does/not/exist.txt"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_no_arguments_prints_usage() {
+    let output = huffman().output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Usage:"), "stderr: {}", stderr);
+    assert!(stderr.contains("compress"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_bad_arguments_exit_with_status_2() {
+    for args in [&["compress", "only-input"][..], &["--bogus", "file"]] {
+        let output = huffman().args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Usage:"), "stderr: {}", stderr);
+    }
+
+    // Runtime failures are not usage errors.
+    let output = huffman().arg("does/not/exist.txt").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_compress_shrinks_repetitive_input() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/compress_input.txt", dir);
+    let output = format!("{}/compress_output.huff", dir);
+    let text = "abracadabra ".repeat(200);
+    fs::write(&input, &text).unwrap();
+
+    let status = huffman()
+        .args(["compress", &input, &output])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let compressed = fs::read(&output).unwrap();
+    assert!(!compressed.is_empty());
+    assert!(compressed.len() < text.len());
+}
+
+#[test]
+fn test_compress_decompress_round_trip() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.txt");
+    let compressed = format!("{}/round_trip.huff", dir);
+    let restored = format!("{}/round_trip.txt", dir);
+
+    let status = huffman()
+        .args(["compress", fixture, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = huffman()
+        .args(["decompress", &compressed, &restored])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&restored).unwrap(), fs::read(fixture).unwrap());
+}
+
+fn pipe_through(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = huffman()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    output.stdout
+}
+
+#[test]
+fn test_compress_decompress_through_stdin_and_stdout() {
+    let text = fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/sample.txt"
+    ))
+    .unwrap();
+
+    let compressed = pipe_through(&["compress", "-", "-"], &text);
+    assert!(compressed.starts_with(b"HUFF"));
+    let restored = pipe_through(&["decompress", "-", "-"], &compressed);
+
+    assert_eq!(restored, text);
+}
+
+#[test]
+fn test_verify_accepts_valid_and_rejects_truncated_file() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.txt");
+    let compressed = format!("{}/verify.huff", dir);
+    let truncated = format!("{}/verify_truncated.huff", dir);
+
+    let status = huffman()
+        .args(["compress", fixture, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = huffman().args(["verify", &compressed]).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("OK"));
+
+    let data = fs::read(&compressed).unwrap();
+    fs::write(&truncated, &data[..data.len() - 3]).unwrap();
+    let output = huffman().args(["verify", &truncated]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+/// Compresses `input` with `flags`, then checks `verify` accepts the result.
+fn assert_verifies(flags: &[&str], input: &str, compressed: &str) {
+    let status = huffman()
+        .arg("compress")
+        .args(flags)
+        .args([input, compressed])
+        .status()
+        .unwrap();
+    assert!(status.success(), "{:?}", flags);
+    let output = huffman().args(["verify", compressed]).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        flags,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("OK"));
+}
+
+#[test]
+fn test_verify_accepts_every_compress_mode() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.txt");
+    let compressed = format!("{}/verify_modes.huff", dir);
+    for flags in [
+        &[][..],
+        &["--mode", "adaptive"],
+        &["--line-mode"],
+        &["--threads", "3"],
+    ] {
+        assert_verifies(flags, fixture, &compressed);
+    }
+}
+
+#[test]
+fn test_table_lists_symbols_by_frequency() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/table_input.txt", dir);
+    fs::write(&input, "aaaabbc\n").unwrap();
+
+    let output = huffman().args(["table", &input]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    let columns: Vec<_> = rows.iter().map(|row| &row[..3]).collect();
+    assert_eq!(
+        columns,
+        vec![
+            ["'a'", "4", "1"],
+            ["'b'", "2", "2"],
+            ["'\\n'", "1", "3"],
+            ["'c'", "1", "3"],
+        ]
+    );
+    for row in &rows {
+        assert_eq!(row[3].len().to_string(), row[2]);
+    }
+}
+
+#[test]
+fn test_incompressible_input_is_stored_and_round_trips() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/stored_input.txt", dir);
+    let compressed = format!("{}/stored.huff", dir);
+    let restored = format!("{}/stored_restored.txt", dir);
+    // Every char occurs once, so the frequency table alone outweighs any
+    // savings and the input is stored as is.
+    let text: String = ('\u{100}'..'\u{200}').collect();
+    fs::write(&input, &text).unwrap();
+
+    let status = huffman()
+        .args(["compress", &input, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    const STORED_BLOCK: u8 = 0;
+    assert_eq!(fs::read(&compressed).unwrap()[6], STORED_BLOCK);
+
+    let status = huffman()
+        .args(["decompress", &compressed, &restored])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read_to_string(&restored).unwrap(), text);
+}
+
+#[test]
+fn test_compress_with_model() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let model = format!("{}/model.tsv", dir);
+    let input = format!("{}/model_input.txt", dir);
+    let compressed = format!("{}/model.huff", dir);
+    let restored = format!("{}/model_restored.txt", dir);
+    fs::write(&model, "a\t10\nb\t3\nc\t1\n\\n\t1\n").unwrap();
+    fs::write(&input, "abcab\nbaaa\n".repeat(20)).unwrap();
+
+    let status = huffman()
+        .args(["compress", "--model", &model, &input, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = huffman()
+        .args(["decompress", &compressed, &restored])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(&restored).unwrap(), fs::read(&input).unwrap());
+
+    // Chars missing from the model cannot be encoded with it.
+    fs::write(&input, "abcz").unwrap();
+    let output = huffman()
+        .args(["compress", "--model", &model, &input, &compressed])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'z'"));
+}
+
+#[test]
+fn test_model_command_writes_loadable_model() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.txt");
+    let model = format!("{}/saved_model.tsv", dir);
+    let compressed = format!("{}/saved_model.huff", dir);
+    let restored = format!("{}/saved_model.txt", dir);
+
+    let status = huffman().args(["model", fixture, &model]).status().unwrap();
+    assert!(status.success());
+    assert!(fs::read_to_string(&model).unwrap().contains("\\n\t"));
+
+    let status = huffman()
+        .args(["compress", "--model", &model, fixture, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = huffman()
+        .args(["decompress", &compressed, &restored])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(&restored).unwrap(), fs::read(fixture).unwrap());
+}
+
+#[test]
+fn test_static_and_adaptive_modes_round_trip() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.txt");
+    let original = fs::read(fixture).unwrap();
+
+    for mode in ["static", "adaptive"] {
+        let compressed = format!("{}/mode_{}.huff", dir, mode);
+        let restored = format!("{}/mode_{}.txt", dir, mode);
+        let status = huffman()
+            .args(["compress", "--mode", mode, fixture, &compressed])
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", mode);
+
+        // Decompression reads the mode from the container.
+        let status = huffman()
+            .args(["decompress", &compressed, &restored])
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", mode);
+        assert_eq!(fs::read(&restored).unwrap(), original, "{}", mode);
+
+        let output = huffman().args(["verify", &compressed]).output().unwrap();
+        assert!(output.status.success(), "{}", mode);
+    }
+
+    let adaptive = fs::read(format!("{}/mode_adaptive.huff", dir)).unwrap();
+    let static_ = fs::read(format!("{}/mode_static.huff", dir)).unwrap();
+    assert_ne!(adaptive[6], static_[6]);
+}
+
+#[test]
+fn test_unknown_mode_is_rejected() {
+    let output = huffman()
+        .args(["compress", "--mode", "lz77", "in", "out"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--mode"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_archive_and_extract_two_files() {
+    let dir = format!("{}/archive_test", env!("CARGO_TARGET_TMPDIR"));
+    let extracted = format!("{}/extracted", dir);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let first = format!("{}/first.txt", dir);
+    let second = format!("{}/second.bin", dir);
+    fs::write(&first, "first file ".repeat(50)).unwrap();
+    fs::write(&second, [0u8, 1, 2, 255, 254, 0, 0, 0]).unwrap();
+    let archive = format!("{}/bundle.hufa", dir);
+
+    let status = huffman()
+        .args(["archive", &archive, &first, &second])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = huffman()
+        .args(["extract", &archive, &extracted])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for (original, name) in [(&first, "first.txt"), (&second, "second.bin")] {
+        assert_eq!(
+            fs::read(format!("{}/{}", extracted, name)).unwrap(),
+            fs::read(original).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_stats_go_to_stderr() {
+    let text = "abracadabra ".repeat(200);
+    let mut child = huffman()
+        .args(["compress", "--stats", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    // Stdout carries only the container, so it still decompresses.
+    let restored = pipe_through(&["decompress", "-", "-"], &output.stdout);
+    assert_eq!(restored, text.as_bytes());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let ratio_line = stderr
+        .lines()
+        .find(|line| line.starts_with("ratio:"))
+        .unwrap_or_else(|| panic!("no ratio line in {:?}", stderr));
+    let ratio: f64 = ratio_line["ratio:".len()..].trim().parse().unwrap();
+    assert!(ratio > 0.0 && ratio < 1.0, "ratio {}", ratio);
+    assert!(stderr.contains(&format!("original:    {} bytes", text.len())));
+}
+
+#[test]
+fn test_progress_reports_file_size_and_matches_plain_output() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/progress_input.txt", dir);
+    let plain = format!("{}/progress_plain.huff", dir);
+    let with_progress = format!("{}/progress.huff", dir);
+    // Several 64 KiB blocks, so progress is reported more than once.
+    let text = "progress is reported once per block\n".repeat(6000);
+    fs::write(&input, &text).unwrap();
+
+    let status = huffman()
+        .args(["compress", &input, &plain])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = huffman()
+        .args(["compress", "--progress", &input, &with_progress])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(fs::read(&with_progress).unwrap(), fs::read(&plain).unwrap());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(lines.len() > 1, "{:?}", stderr);
+    let last = format!("progress:    {0} of {0} bytes (100%)", text.len());
+    assert_eq!(lines.last(), Some(&last.as_str()));
+
+    // Stdin has no known size, so only the processed bytes are shown.
+    let mut child = huffman()
+        .args(["compress", "--progress", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, fs::read(&plain).unwrap());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let last = format!("progress:    {} bytes", text.len());
+    assert_eq!(stderr.lines().last(), Some(last.as_str()));
+
+    let adaptive = huffman()
+        .args([
+            "compress",
+            "--progress",
+            "--mode",
+            "adaptive",
+            &input,
+            &plain,
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(adaptive.status.code(), Some(1));
+}
+
+#[test]
+fn test_empty_file_round_trip() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/empty_input.txt", dir);
+    let compressed = format!("{}/empty.huff", dir);
+    let restored = format!("{}/empty_restored.txt", dir);
+    fs::write(&input, "").unwrap();
+
+    for mode in ["static", "adaptive"] {
+        let status = huffman()
+            .args(["compress", "--mode", mode, &input, &compressed])
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", mode);
+        assert!(!fs::read(&compressed).unwrap().is_empty());
+
+        fs::write(&restored, "stale").unwrap();
+        let status = huffman()
+            .args(["decompress", &compressed, &restored])
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", mode);
+        assert_eq!(fs::read(&restored).unwrap(), b"");
+    }
+}
+
+#[test]
+fn test_line_mode_decompresses_single_lines() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/lines_input.log", dir);
+    let compressed = format!("{}/lines.huff", dir);
+    let text = "GET /index.html 200\nGET /missing 404\nPOST /login 302\n";
+    fs::write(&input, text).unwrap();
+
+    let status = huffman()
+        .args(["compress", "--line-mode", &input, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let line = huffman()
+        .args(["decompress", "--line", "1", &compressed, "-"])
+        .output()
+        .unwrap();
+    assert!(line.status.success());
+    assert_eq!(line.stdout, b"GET /missing 404\n");
+
+    let all = huffman()
+        .args(["decompress", &compressed, "-"])
+        .output()
+        .unwrap();
+    assert_eq!(all.stdout, text.as_bytes());
+
+    let out_of_range = huffman()
+        .args(["decompress", "--line", "3", &compressed, "-"])
+        .output()
+        .unwrap();
+    assert_eq!(out_of_range.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out_of_range.stderr);
+    assert!(stderr.contains("out of range"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_record_size_round_trips_binary_records() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/records_input.bin", dir);
+    let compressed = format!("{}/records.huff", dir);
+    let data: Vec<u8> = (0..500u32)
+        .flat_map(|i| {
+            let mut record = i.to_le_bytes().to_vec();
+            record.extend_from_slice(&[0xFF, (i % 3) as u8]);
+            record
+        })
+        .collect();
+    fs::write(&input, &data).unwrap();
+
+    let status = huffman()
+        .args(["compress", "--record-size", "6", &input, &compressed])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::metadata(&compressed).unwrap().len() < data.len() as u64);
+
+    let output = huffman()
+        .args(["decompress", &compressed, "-"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, data);
+
+    let output = huffman().args(["verify", &compressed]).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("OK (3000 bytes decoded)"));
+
+    let too_large = huffman()
+        .args(["compress", "--record-size", "300", &input, &compressed])
+        .output()
+        .unwrap();
+    assert_eq!(too_large.status.code(), Some(1));
+
+    let with_threads = huffman()
+        .args(["compress", "--record-size", "6", "--threads", "2"])
+        .args([&input, &compressed])
+        .output()
+        .unwrap();
+    assert_eq!(with_threads.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&with_threads.stderr).contains("--threads"));
+}
+
+#[test]
+fn test_symbol_width_round_trips_binary_input() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/utf16_input.bin", dir);
+    let compressed = format!("{}/utf16.huff", dir);
+    let data: Vec<u8> = "ünïcödé text, ünïcödé text "
+        .repeat(40)
+        .encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes())
+        .collect();
+    fs::write(&input, &data).unwrap();
+
+    for width in ["8", "16"] {
+        let status = huffman()
+            .args(["compress", "--symbol-width", width, &input, &compressed])
+            .status()
+            .unwrap();
+        assert!(status.success(), "width {}", width);
+        assert!(fs::metadata(&compressed).unwrap().len() < data.len() as u64);
+
+        let output = huffman()
+            .args(["decompress", &compressed, "-"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "width {}", width);
+        assert_eq!(output.stdout, data, "width {}", width);
+
+        let output = huffman().args(["verify", &compressed]).output().unwrap();
+        assert!(output.status.success(), "width {}", width);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("OK"));
+    }
+
+    fs::write(&input, b"odd").unwrap();
+    let odd = huffman()
+        .args(["compress", "--symbol-width", "16", &input, &compressed])
+        .output()
+        .unwrap();
+    assert_eq!(odd.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&odd.stderr).contains("odd number of bytes"));
+
+    let bad = huffman()
+        .args(["compress", "--symbol-width", "32", &input, &compressed])
+        .output()
+        .unwrap();
+    assert_eq!(bad.status.code(), Some(2));
+}
+
+#[test]
+fn test_char_count_escapes_control_and_combining_chars() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/escaped_chars.txt", dir);
+    fs::write(&input, "a\tb\ne\u{301}\0").unwrap();
+
+    let output = huffman().arg(&input).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "Character Frequency:\n'\\0': 1\n'\\t': 1\n'\\n': 1\n'a': 1\n'b': 1\n'e': 1\n'\\u{301}': 1\n"
+    );
+}
+
+#[test]
+fn test_char_count_sorts_by_frequency() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/sorted_chars.txt", dir);
+    fs::write(&input, "abbcccbbcddd").unwrap();
+
+    let by_freq = huffman().args(["--sort", "freq", &input]).output().unwrap();
+    assert!(by_freq.status.success());
+    assert_eq!(
+        String::from_utf8(by_freq.stdout).unwrap(),
+        "Character Frequency:\n'b': 4\n'c': 4\n'd': 3\n'a': 1\n"
+    );
+
+    let by_char = huffman().args(["--sort", "char", &input]).output().unwrap();
+    assert_eq!(
+        String::from_utf8(by_char.stdout).unwrap(),
+        "Character Frequency:\n'a': 1\n'b': 4\n'c': 4\n'd': 3\n"
+    );
+
+    let bad = huffman().args(["--sort", "size", &input]).output().unwrap();
+    assert_eq!(bad.status.code(), Some(2));
+}
+
+#[test]
+fn test_threads_compresses_in_parallel_blocks() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/threads_input.txt", dir);
+    let text = "parallel blocks share one code, 🎉 ünïcödé\n".repeat(500);
+    fs::write(&input, &text).unwrap();
+
+    for threads in ["1", "4"] {
+        let compressed = format!("{}/threads_{}.huff", dir, threads);
+        let status = huffman()
+            .args(["compress", "--threads", threads, &input, &compressed])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        for decode_threads in [&["--threads", "3"][..], &[]] {
+            let output = huffman()
+                .arg("decompress")
+                .args(decode_threads)
+                .args([&compressed, "-"])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            assert_eq!(output.stdout, text.as_bytes());
+        }
+    }
+
+    let zero = huffman()
+        .args(["compress", "--threads", "0", &input, "-"])
+        .output()
+        .unwrap();
+    assert_eq!(zero.status.code(), Some(2));
+}
+
+#[test]
+fn test_entropy_prints_bound_for_known_distribution() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/entropy_input.txt", dir);
+    // Probabilities 1/2, 1/4, 1/8, 1/8: 1.75 bits per char, 14 bits in all.
+    fs::write(&input, "aaaabbcd").unwrap();
+
+    let output = huffman().args(["entropy", &input]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "entropy:     1.750 bits/symbol\nsymbols:     8\nminimum:     2 bytes (input is 8 bytes)\n"
+    );
+}